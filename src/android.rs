@@ -1,17 +1,37 @@
-use crate::{Browser, Error, ErrorKind, Result};
+use crate::{Browser, BrowserOptions, Error, ErrorKind, OpenStatus, Result};
 pub use std::os::unix::process::ExitStatusExt;
-use std::process::{Command, ExitStatus};
+use std::process::Command;
 
 /// Deal with opening of browsers on Android
 #[inline]
-pub fn open_browser_internal(_: Browser, url: &str) -> Result<ExitStatus> {
-    Command::new("am")
-        .arg("start")
-        .arg("--user")
-        .arg("0")
-        .arg("-a")
-        .arg("android.intent.action.VIEW")
-        .arg("-d")
-        .arg(url)
-        .status()
+pub fn open_browser_internal(
+    browser: Browser,
+    url: &str,
+    options: &BrowserOptions,
+) -> Result<OpenStatus> {
+    if options.incognito {
+        return Err(Error::new(
+            ErrorKind::Unsupported,
+            "incognito mode is not supported on this platform",
+        ));
+    }
+    if browser == Browser::Terminal {
+        return Err(crate::unsupported_browser(browser));
+    }
+    options.run(
+        Command::new("am")
+            .arg("start")
+            .arg("--user")
+            .arg("0")
+            .arg("-a")
+            .arg("android.intent.action.VIEW")
+            .arg("-d")
+            .arg(url),
+    )
+}
+
+/// Returns true if there's something which can open `browser`. Urls are always handed over to
+/// the system via `am start`, which doesn't do text browsers.
+pub fn is_available_internal(browser: Browser) -> bool {
+    browser != Browser::Terminal
 }
@@ -0,0 +1,177 @@
+use crate::{Error, ErrorKind, Result};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Largest decoded payload of a data: URL that will be written out
+pub(crate) const MAX_DATA_URL_PAYLOAD: usize = 16 * 1024 * 1024;
+
+/// Media types we know how to hand over to a browser as a file, with the extension to use
+const EXTENSIONS: &[(&str, &str)] = &[
+    ("text/html", "html"),
+    ("application/xhtml+xml", "xhtml"),
+    ("text/plain", "txt"),
+    ("text/css", "css"),
+    ("application/json", "json"),
+    ("application/pdf", "pdf"),
+    ("image/png", "png"),
+    ("image/jpeg", "jpg"),
+    ("image/gif", "gif"),
+    ("image/webp", "webp"),
+    ("image/svg+xml", "svg"),
+];
+
+/// Returns true if `url` uses the data: scheme
+pub(crate) fn is_data_url(url: &str) -> bool {
+    url.get(..5)
+        .map(|scheme| scheme.eq_ignore_ascii_case("data:"))
+        .unwrap_or(false)
+}
+
+/// Decodes the data: URL `url` into a new file in the temp dir, and returns its path. The file is
+/// left behind, as there's no telling when the browser is done with it.
+pub(crate) fn materialize(url: &str) -> Result<PathBuf> {
+    let (extension, payload) = decode(url)?;
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let path = std::env::temp_dir().join(format!(
+        "webbrowser-data-{}-{}.{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed),
+        extension
+    ));
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)?
+        .write_all(&payload)?;
+    Ok(path)
+}
+
+/// Splits a data: URL into the file extension for its media type, and its decoded payload
+fn decode(url: &str) -> Result<(&'static str, Vec<u8>)> {
+    let invalid = |msg: &str| Error::new(ErrorKind::InvalidInput, format!("{} in data: url", msg));
+    let (header, data) = url[5..]
+        .split_once(',')
+        .ok_or_else(|| invalid("missing ','"))?;
+    let mut params = header.split(';');
+    let media_type = params
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    let is_base64 = params.any(|param| param.trim().eq_ignore_ascii_case("base64"));
+    let media_type = if media_type.is_empty() {
+        "text/plain"
+    } else {
+        &media_type
+    };
+    let extension = EXTENSIONS
+        .iter()
+        .find(|(known, _)| *known == media_type)
+        .map(|(_, extension)| *extension)
+        .ok_or_else(|| {
+            Error::new(
+                ErrorKind::Unsupported,
+                format!("unsupported media type {} in data: url", media_type),
+            )
+        })?;
+    // payloads are capped before decoding, which can only make them smaller
+    if data.len() > MAX_DATA_URL_PAYLOAD * 4 / 3 + 4 {
+        return Err(invalid("payload too large"));
+    }
+    let data = percent_decode(data).ok_or_else(|| invalid("bad percent-encoding"))?;
+    let payload = if is_base64 {
+        base64_decode(&data).ok_or_else(|| invalid("bad base64"))?
+    } else {
+        data
+    };
+    if payload.len() > MAX_DATA_URL_PAYLOAD {
+        return Err(invalid("payload too large"));
+    }
+    Ok((extension, payload))
+}
+
+/// Decodes %XX escapes, returning None if any are malformed
+fn percent_decode(data: &str) -> Option<Vec<u8>> {
+    let bytes = data.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    Some(decoded)
+}
+
+/// Decodes standard (or URL-safe) base64, ignoring whitespace and padding. Returns None if there
+/// are any other characters in there.
+fn base64_decode(data: &[u8]) -> Option<Vec<u8>> {
+    let sextet = |c: u8| match c {
+        b'A'..=b'Z' => Some(c - b'A'),
+        b'a'..=b'z' => Some(c - b'a' + 26),
+        b'0'..=b'9' => Some(c - b'0' + 52),
+        b'+' | b'-' => Some(62),
+        b'/' | b'_' => Some(63),
+        _ => None,
+    };
+    let mut decoded = Vec::with_capacity(data.len() * 3 / 4);
+    let (mut bits, mut nbits) = (0u32, 0);
+    for &c in data
+        .iter()
+        .filter(|c| !c.is_ascii_whitespace() && **c != b'=')
+    {
+        bits = (bits << 6) | u32::from(sextet(c)?);
+        nbits += 6;
+        if nbits >= 8 {
+            nbits -= 8;
+            decoded.push((bits >> nbits) as u8);
+        }
+    }
+    Some(decoded)
+}
+
+#[test]
+fn test_decode_base64_html() {
+    // "<h1>hi</h1>"
+    let (extension, payload) = decode("data:text/html;base64,PGgxPmhpPC9oMT4=").unwrap();
+    assert_eq!(extension, "html");
+    assert_eq!(payload, b"<h1>hi</h1>");
+}
+
+#[test]
+fn test_decode_percent_encoded() {
+    let (extension, payload) = decode("data:text/html;charset=utf-8,%3Cp%3Ea%20b%3C/p%3E").unwrap();
+    assert_eq!(extension, "html");
+    assert_eq!(payload, b"<p>a b</p>");
+    let (extension, payload) = decode("data:,hello").unwrap();
+    assert_eq!(extension, "txt");
+    assert_eq!(payload, b"hello");
+}
+
+#[test]
+fn test_decode_errors() {
+    let kind = |url| decode(url).unwrap_err().kind();
+    assert_eq!(kind("data:text/html"), ErrorKind::InvalidInput);
+    assert_eq!(kind("data:application/x-foo,abc"), ErrorKind::Unsupported);
+    assert_eq!(kind("data:text/html;base64,!!!"), ErrorKind::InvalidInput);
+    assert_eq!(kind("data:text/plain,%zz"), ErrorKind::InvalidInput);
+    let huge = format!("data:text/plain,{}", "a".repeat(MAX_DATA_URL_PAYLOAD + 1));
+    assert_eq!(kind(&huge), ErrorKind::InvalidInput);
+}
+
+#[test]
+fn test_materialize() {
+    assert!(is_data_url("DATA:text/html,x"));
+    assert!(!is_data_url("http://example.com"));
+    let path = materialize("data:text/html;base64,PGgxPmhpPC9oMT4=").unwrap();
+    assert_eq!(path.extension().unwrap(), "html");
+    assert_eq!(std::fs::read(&path).unwrap(), b"<h1>hi</h1>");
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,137 @@
+//! A C-friendly entry point, for bindings in other languages. Enabled via the `ffi` feature.
+//!
+//! ```c
+//! if (open_c("http://github.com") != WEBBROWSER_OK) {
+//!     fprintf(stderr, "failed to open: %s\n", last_error_message());
+//! }
+//! ```
+
+use crate::Error;
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::io::ErrorKind;
+use std::os::raw::c_char;
+use std::ptr;
+
+/// The URL was opened
+pub const WEBBROWSER_OK: i32 = 0;
+/// The URL pointer was null, or didn't point to valid UTF-8
+pub const WEBBROWSER_ERR_INVALID_ARGUMENT: i32 = -1;
+/// No browser was found to open the URL in
+pub const WEBBROWSER_ERR_NOT_FOUND: i32 = -2;
+/// The URL was rejected, e.g. for being too long
+pub const WEBBROWSER_ERR_INVALID_INPUT: i32 = -3;
+/// Opening the URL isn't supported on this platform
+pub const WEBBROWSER_ERR_UNSUPPORTED: i32 = -4;
+/// Not allowed to launch the browser
+pub const WEBBROWSER_ERR_PERMISSION_DENIED: i32 = -5;
+/// Any other failure
+pub const WEBBROWSER_ERR_OTHER: i32 = -99;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Opens the null-terminated `url` in the default browser, as with [open](../fn.open.html).
+/// Returns [WEBBROWSER_OK](constant.WEBBROWSER_OK.html) on success, or one of the negative
+/// `WEBBROWSER_ERR_*` codes otherwise, in which case [last_error_message](fn.last_error_message.html)
+/// describes what went wrong.
+///
+/// # Safety
+/// `url` must either be null, or point to a null-terminated string which stays valid for the
+/// duration of the call.
+#[no_mangle]
+pub unsafe extern "C" fn open_c(url: *const c_char) -> i32 {
+    if url.is_null() {
+        return fail(
+            WEBBROWSER_ERR_INVALID_ARGUMENT,
+            &Error::new(ErrorKind::InvalidInput, "url is null"),
+        );
+    }
+    let url = match CStr::from_ptr(url).to_str() {
+        Ok(url) => url,
+        Err(e) => {
+            return fail(
+                WEBBROWSER_ERR_INVALID_ARGUMENT,
+                &Error::new(ErrorKind::InvalidInput, e),
+            )
+        }
+    };
+    match crate::open(url) {
+        Ok(_) => {
+            LAST_ERROR.with(|last| *last.borrow_mut() = None);
+            WEBBROWSER_OK
+        }
+        Err(e) => fail(error_code(&e), &e),
+    }
+}
+
+/// Returns a description of the last error on this thread from [open_c](fn.open_c.html), or null
+/// if the last call succeeded (or there wasn't one). The string is owned by this crate, and stays
+/// valid until the next call to `open_c` on the same thread.
+#[no_mangle]
+pub extern "C" fn last_error_message() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// Maps an error to its `WEBBROWSER_ERR_*` code
+fn error_code(err: &Error) -> i32 {
+    match err.kind() {
+        ErrorKind::NotFound => WEBBROWSER_ERR_NOT_FOUND,
+        ErrorKind::InvalidInput => WEBBROWSER_ERR_INVALID_INPUT,
+        ErrorKind::Unsupported => WEBBROWSER_ERR_UNSUPPORTED,
+        ErrorKind::PermissionDenied => WEBBROWSER_ERR_PERMISSION_DENIED,
+        _ => WEBBROWSER_ERR_OTHER,
+    }
+}
+
+/// Records `err` as the last error, and returns `code`
+fn fail(code: i32, err: &Error) -> i32 {
+    // interior nul bytes can't be represented, so they're dropped from the message
+    let message = CString::new(err.to_string().replace('\0', "")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+    code
+}
+
+#[test]
+fn test_open_c_null() {
+    assert_eq!(
+        unsafe { open_c(ptr::null()) },
+        WEBBROWSER_ERR_INVALID_ARGUMENT
+    );
+    let message = unsafe { CStr::from_ptr(last_error_message()) };
+    assert_eq!(message.to_str().unwrap(), "url is null");
+}
+
+#[test]
+fn test_open_c_invalid_utf8() {
+    let url = CString::new(vec![0x68, 0xff, 0xfe]).unwrap();
+    assert_eq!(
+        unsafe { open_c(url.as_ptr()) },
+        WEBBROWSER_ERR_INVALID_ARGUMENT
+    );
+    assert!(!last_error_message().is_null());
+}
+
+#[test]
+fn test_open_c_invalid_url() {
+    let url = CString::new("http://example.com/<script>").unwrap();
+    assert_eq!(
+        unsafe { open_c(url.as_ptr()) },
+        WEBBROWSER_ERR_INVALID_INPUT
+    );
+    assert!(!last_error_message().is_null());
+}
+
+#[test]
+fn test_error_code() {
+    assert_eq!(
+        error_code(&Error::new(ErrorKind::NotFound, "x")),
+        WEBBROWSER_ERR_NOT_FOUND
+    );
+    assert_eq!(error_code(&Error::other("x")), WEBBROWSER_ERR_OTHER);
+}
@@ -0,0 +1,388 @@
+use crate::{BrowserOptions, Error, ErrorKind, Result};
+use std::path::Path;
+
+/// Families of browsers sharing the same command line conventions
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub(crate) enum Family {
+    Firefox,
+    Chromium,
+    Edge,
+    Opera,
+}
+
+impl Family {
+    /// Works out the family from the command (a bare name, or a path) which launches the
+    /// browser, or from its application name on macOS
+    pub(crate) fn from_command(cmd: &str) -> Option<Family> {
+        let name = Path::new(cmd)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or(cmd)
+            .to_lowercase()
+            .replace(' ', "-");
+        if name.starts_with("firefox") {
+            Some(Family::Firefox)
+        } else if name.contains("edge") {
+            Some(Family::Edge)
+        } else if name.starts_with("opera") {
+            Some(Family::Opera)
+        } else if ["chrom", "google-chrome", "brave", "vivaldi"]
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+        {
+            Some(Family::Chromium)
+        } else {
+            None
+        }
+    }
+
+    fn is_chromium_based(self) -> bool {
+        self != Family::Firefox
+    }
+}
+
+/// Returns the extra command line arguments with which `cmd` should be launched to honour
+/// `options`, or an error if `options` asks for something the browser can't do. `is_running` is
+/// only called if it's needed to find out whether the browser is running already, and `var` to
+/// look up env vars.
+pub(crate) fn browser_args(
+    cmd: &str,
+    options: &BrowserOptions,
+    is_running: impl FnOnce() -> bool,
+    var: impl Fn(&str) -> Option<String>,
+) -> Result<Vec<String>> {
+    let family = Family::from_command(cmd);
+    let mut args: Vec<String> = Vec::new();
+    if options.incognito {
+        let flag = match family {
+            Some(Family::Firefox) => "-private-window",
+            Some(Family::Chromium) => "--incognito",
+            Some(Family::Edge) => "--inprivate",
+            Some(Family::Opera) => "--private",
+            None => {
+                return Err(Error::new(
+                    ErrorKind::Unsupported,
+                    format!("incognito mode is not supported for {}", cmd),
+                ))
+            }
+        };
+        args.push(flag.to_string());
+    }
+    let proxy = match options.proxy {
+        Some(ref proxy) => Some((proxy.clone(), None)),
+        None if options.inherit_proxy_env => proxy_from_env(var),
+        None => None,
+    };
+    if let Some((proxy, bypass)) = proxy {
+        match family {
+            Some(family) if family.is_chromium_based() => {
+                args.push(format!("--proxy-server={}", proxy));
+                if let Some(bypass) = bypass {
+                    args.push(format!("--proxy-bypass-list={}", bypass));
+                }
+            }
+            // Firefox inherits the environment, which it goes by with the (default) system proxy
+            // settings, so that's as much as can be done for it
+            _ => log::debug!(
+                "ignoring proxy {} as it can't be set via the command line for {}",
+                proxy,
+                cmd
+            ),
+        }
+    }
+    if let Some(ref locale) = options.locale {
+        if family.map(Family::is_chromium_based).unwrap_or(false) {
+            args.push(format!("--lang={}", language_tag(locale)));
+        }
+    }
+    if let Some(screen) = options.screen_hint {
+        match (family, options.screen_origins.get(screen)) {
+            (Some(family), Some(&(x, y))) if family.is_chromium_based() => {
+                args.push(window_position(x, y))
+            }
+            (_, None) => log::debug!("ignoring screen {}, as its position isn't known", screen),
+            _ => log::debug!("ignoring screen {}, as {} can't be placed", screen, cmd),
+        }
+    }
+    // Firefox takes the url as the value of these, so they have to come last. A private window is
+    // always a new one, and Firefox won't take both.
+    if family == Some(Family::Firefox) && !options.incognito {
+        if options.new_window {
+            args.push("-new-window".to_string());
+        } else if options.force_tab_in_running.unwrap_or_else(is_running) {
+            // Chromium-family browsers hand over to a running instance anyway
+            args.push("-new-tab".to_string());
+        }
+    } else if options.new_window {
+        match family {
+            Some(family) if family.is_chromium_based() => args.push("--new-window".to_string()),
+            _ => log::debug!("ignoring new window, as it isn't supported for {}", cmd),
+        }
+    }
+    Ok(args)
+}
+
+/// Returns the flag which puts the window of a Chromium-family browser at `x`,`y`. A little is
+/// added to the corner of the screen, so that the window is clearly within the screen, and the
+/// window manager doesn't put it on the neighbouring one.
+fn window_position(x: i32, y: i32) -> String {
+    const INSET: i32 = 10;
+    format!(
+        "--window-position={},{}",
+        x.saturating_add(INSET),
+        y.saturating_add(INSET)
+    )
+}
+
+/// Works out the proxy (in the form `--proxy-server` takes) from the conventional env vars, along
+/// with the hosts to bypass it for. Different proxies for http and https are given per scheme.
+fn proxy_from_env(var: impl Fn(&str) -> Option<String>) -> Option<(String, Option<String>)> {
+    // the lowercase ones are the more widely honoured, so they win
+    let var = |name: &str| {
+        var(&name.to_lowercase())
+            .or_else(|| var(name))
+            .filter(|value| !value.trim().is_empty())
+    };
+    let proxy = match (var("HTTP_PROXY"), var("HTTPS_PROXY")) {
+        (Some(http), Some(https)) if http != https => format!("http={};https={}", http, https),
+        (Some(proxy), _) | (None, Some(proxy)) => proxy,
+        (None, None) => return None,
+    };
+    let bypass = var("NO_PROXY").map(|hosts| {
+        hosts
+            .split(',')
+            .map(str::trim)
+            .filter(|host| !host.is_empty())
+            .collect::<Vec<_>>()
+            .join(";")
+    });
+    Some((proxy, bypass))
+}
+
+/// Turns a POSIX locale (`de_DE.UTF-8`) into the language tag (`de-DE`) Chromium expects
+fn language_tag(locale: &str) -> String {
+    locale
+        .split(['.', '@'])
+        .next()
+        .unwrap_or(locale)
+        .replace('_', "-")
+}
+
+#[test]
+fn test_family_from_command() {
+    assert_eq!(Family::from_command("firefox"), Some(Family::Firefox));
+    assert_eq!(
+        Family::from_command("/usr/lib/firefox-esr/firefox-esr"),
+        Some(Family::Firefox)
+    );
+    assert_eq!(
+        Family::from_command("google-chrome-stable"),
+        Some(Family::Chromium)
+    );
+    assert_eq!(
+        Family::from_command("Google Chrome"),
+        Some(Family::Chromium)
+    );
+    assert_eq!(
+        Family::from_command("/usr/bin/chromium"),
+        Some(Family::Chromium)
+    );
+    assert_eq!(
+        Family::from_command("brave-browser"),
+        Some(Family::Chromium)
+    );
+    assert_eq!(
+        Family::from_command("vivaldi-stable"),
+        Some(Family::Chromium)
+    );
+    assert_eq!(Family::from_command("microsoft-edge"), Some(Family::Edge));
+    assert_eq!(Family::from_command("opera"), Some(Family::Opera));
+    assert_eq!(Family::from_command("xdg-open"), None);
+}
+
+#[test]
+fn test_incognito_args() {
+    let mut options = BrowserOptions::new();
+    options.incognito(true);
+    let args = |cmd| browser_args(cmd, &options, || false, |_| None).unwrap();
+    assert_eq!(args("/opt/firefox/firefox"), vec!["-private-window"]);
+    assert_eq!(args("chromium-browser"), vec!["--incognito"]);
+    assert_eq!(args("brave"), vec!["--incognito"]);
+    assert_eq!(args("msedge"), vec!["--inprivate"]);
+    assert_eq!(args("opera"), vec!["--private"]);
+}
+
+#[test]
+fn test_incognito_unsupported() {
+    let mut options = BrowserOptions::new();
+    options.incognito(true);
+    let err = browser_args("/usr/bin/mybrowser", &options, || false, |_| None).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Unsupported);
+    assert!(err.to_string().contains("mybrowser"));
+}
+
+#[test]
+fn test_proxy_args() {
+    let mut options = BrowserOptions::new();
+    options.proxy("localhost:3128");
+    assert_eq!(
+        browser_args("chromium", &options, || false, |_| None).unwrap(),
+        vec!["--proxy-server=localhost:3128"]
+    );
+    assert!(browser_args("firefox", &options, || false, |_| None)
+        .unwrap()
+        .is_empty());
+    assert!(browser_args("WebPositive", &options, || false, |_| None)
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn test_force_tab_in_running_args() {
+    let mut options = BrowserOptions::new();
+    options.force_tab_in_running(true);
+    assert_eq!(
+        browser_args("firefox", &options, || false, |_| None).unwrap(),
+        vec!["-new-tab"]
+    );
+    assert!(browser_args("google-chrome", &options, || false, |_| None)
+        .unwrap()
+        .is_empty());
+    // a private window is always opened in the running instance
+    options.incognito(true);
+    assert_eq!(
+        browser_args("firefox", &options, || false, |_| None).unwrap(),
+        vec!["-private-window"]
+    );
+}
+
+#[test]
+fn test_new_tab_only_when_running() {
+    let options = BrowserOptions::new();
+    assert_eq!(
+        browser_args("firefox", &options, || true, |_| None).unwrap(),
+        vec!["-new-tab"]
+    );
+    assert!(browser_args("firefox", &options, || false, |_| None)
+        .unwrap()
+        .is_empty());
+    let mut options = BrowserOptions::new();
+    options.force_tab_in_running(false);
+    assert!(
+        browser_args("firefox", &options, || panic!("not needed"), |_| None)
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn test_locale_args() {
+    let mut options = BrowserOptions::new();
+    options.locale("de_DE.UTF-8");
+    assert_eq!(
+        browser_args("chromium", &options, || false, |_| None).unwrap(),
+        vec!["--lang=de-DE"]
+    );
+    // Firefox goes by the environment instead
+    assert!(browser_args("firefox", &options, || false, |_| None)
+        .unwrap()
+        .is_empty());
+    assert_eq!(language_tag("sr_RS@latin"), "sr-RS");
+    assert_eq!(language_tag("fr"), "fr");
+}
+
+#[test]
+fn test_new_window_args() {
+    let mut options = BrowserOptions::new();
+    options.new_window(true);
+    let args =
+        |cmd, options: &BrowserOptions| browser_args(cmd, options, || true, |_| None).unwrap();
+    assert_eq!(args("firefox", &options), vec!["-new-window"]);
+    assert_eq!(args("chromium", &options), vec!["--new-window"]);
+    assert_eq!(args("msedge", &options), vec!["--new-window"]);
+    assert!(args("WebPositive", &options).is_empty());
+
+    options.incognito(true);
+    assert_eq!(args("firefox", &options), vec!["-private-window"]);
+    assert_eq!(
+        args("chromium", &options),
+        vec!["--incognito", "--new-window"]
+    );
+}
+
+#[test]
+fn test_screen_hint_args() {
+    let mut options = BrowserOptions::new();
+    options
+        .screen_origins(vec![(0, 0), (1920, 0), (-1280, -200)])
+        .screen_hint(1);
+    assert_eq!(
+        browser_args("google-chrome", &options, || false, |_| None).unwrap(),
+        vec!["--window-position=1930,10"]
+    );
+    options.screen_hint(2);
+    assert_eq!(
+        browser_args("msedge", &options, || false, |_| None).unwrap(),
+        vec!["--window-position=-1270,-190"]
+    );
+    assert!(browser_args("firefox", &options, || false, |_| None)
+        .unwrap()
+        .is_empty());
+    // a screen which isn't in the layout
+    options.screen_hint(3);
+    assert!(browser_args("chromium", &options, || false, |_| None)
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn test_proxy_from_env() {
+    let env = |vars: &'static [(&'static str, &'static str)]| {
+        move |key: &str| {
+            vars.iter()
+                .find(|(name, _)| *name == key)
+                .map(|(_, value)| value.to_string())
+        }
+    };
+    assert_eq!(
+        proxy_from_env(env(&[("HTTPS_PROXY", "http://proxy:3128")])),
+        Some(("http://proxy:3128".to_string(), None))
+    );
+    assert_eq!(
+        proxy_from_env(env(&[
+            ("HTTP_PROXY", "http://a:1"),
+            ("https_proxy", "http://b:2"),
+            ("HTTPS_PROXY", "http://c:3"),
+            ("NO_PROXY", "localhost, .internal"),
+        ])),
+        Some((
+            "http=http://a:1;https=http://b:2".to_string(),
+            Some("localhost;.internal".to_string())
+        ))
+    );
+    assert_eq!(proxy_from_env(env(&[("NO_PROXY", "localhost")])), None);
+}
+
+#[test]
+fn test_inherit_proxy_env_args() {
+    let var = |key: &str| match key {
+        "HTTPS_PROXY" => Some("http://proxy.example.com:3128".to_string()),
+        _ => None,
+    };
+    let mut options = BrowserOptions::new();
+    assert!(browser_args("chromium", &options, || false, var)
+        .unwrap()
+        .is_empty());
+    options.inherit_proxy_env(true);
+    let args = browser_args("chromium", &options, || false, var).unwrap();
+    assert_eq!(args, vec!["--proxy-server=http://proxy.example.com:3128"]);
+    assert!(browser_args("firefox", &options, || false, var)
+        .unwrap()
+        .is_empty());
+    // an explicit proxy wins
+    options.proxy("localhost:8080");
+    assert_eq!(
+        browser_args("chromium", &options, || false, var).unwrap(),
+        vec!["--proxy-server=localhost:8080"]
+    );
+}
@@ -0,0 +1,135 @@
+//! Opens URLs via `UIApplication`, as an app on iOS (or Mac Catalyst) has no `open` command to
+//! turn to. The Objective-C side is reached through `objc2` and `block2`, which come with the
+//! `ios` feature.
+
+use crate::{Browser, BrowserOptions, Error, ErrorKind, OpenStatus, Result};
+use block2::RcBlock;
+use objc2::rc::{Allocated, Retained};
+use objc2::runtime::{AnyObject, Bool};
+use objc2::{class, msg_send, MainThreadMarker};
+use std::cell::Cell;
+use std::os::raw::c_void;
+pub use std::os::unix::process::ExitStatusExt;
+use std::process::Output;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// How long to wait for UIKit to tell whether the URL could be opened
+const ANSWER_TIMEOUT: Duration = Duration::from_secs(5);
+
+const NS_UTF8_STRING_ENCODING: usize = 4;
+
+// fails the build if this backend ever stops providing the crate's `open`
+const _: fn(&str) -> Result<Output> = crate::open;
+
+#[link(name = "UIKit", kind = "framework")]
+extern "C" {}
+
+/// Deal with opening of URLs on iOS, via `-[UIApplication openURL:options:completionHandler:]`.
+/// UIKit only allows this on the main thread, so it's an error to call this from anywhere else.
+#[inline]
+pub fn open_browser_internal(
+    browser: Browser,
+    url: &str,
+    options: &BrowserOptions,
+) -> Result<OpenStatus> {
+    if browser != Browser::Default {
+        return Err(crate::unsupported_browser(browser));
+    }
+    if options.incognito {
+        return Err(Error::new(
+            ErrorKind::Unsupported,
+            "incognito mode is not supported on this platform",
+        ));
+    }
+    if options.dry_run {
+        options.report_dry_run("UIApplication.openURL", &[url.to_string()]);
+        return Ok(OpenStatus::launched());
+    }
+    open_url(url)
+}
+
+/// Returns true if there's something which can open `browser`. Only the system can pick which
+/// app opens a URL here.
+pub fn is_available_internal(browser: Browser) -> bool {
+    browser == Browser::Default
+}
+
+fn open_url(url: &str) -> Result<OpenStatus> {
+    if MainThreadMarker::new().is_none() {
+        return Err(Error::other(
+            "URLs can only be opened from the main thread on this platform",
+        ));
+    }
+    let app: Option<Retained<AnyObject>> =
+        unsafe { msg_send![class!(UIApplication), sharedApplication] };
+    let app = app.ok_or_else(|| {
+        Error::new(
+            ErrorKind::NotFound,
+            "there's no UIApplication to open URLs with",
+        )
+    })?;
+
+    let ns_url = unsafe {
+        let string: Allocated<AnyObject> = msg_send![class!(NSString), alloc];
+        let string: Option<Retained<AnyObject>> = msg_send![
+            string,
+            initWithBytes: url.as_ptr() as *const c_void,
+            length: url.len(),
+            encoding: NS_UTF8_STRING_ENCODING,
+        ];
+        match string {
+            Some(string) => {
+                let ns_url: Option<Retained<AnyObject>> =
+                    msg_send![class!(NSURL), URLWithString: &*string];
+                ns_url
+            }
+            None => None,
+        }
+    };
+    let ns_url = ns_url.ok_or_else(|| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("{} is not a URL UIKit can open", url),
+        )
+    })?;
+
+    // the handler may come after we've stopped waiting for it, so it holds on to the answer too
+    let answer = Rc::new(Cell::new(None));
+    let handler = {
+        let answer = Rc::clone(&answer);
+        RcBlock::new(move |success: Bool| answer.set(Some(success.as_bool())))
+    };
+    unsafe {
+        let options: Retained<AnyObject> = msg_send![class!(NSDictionary), dictionary];
+        let _: () = msg_send![
+            &*app,
+            openURL: &*ns_url,
+            options: &*options,
+            completionHandler: &*handler,
+        ];
+    }
+
+    // the handler is dispatched to the main queue, which only gets to run with the run loop
+    let run_loop: Retained<AnyObject> = unsafe { msg_send![class!(NSRunLoop), currentRunLoop] };
+    let deadline = Instant::now() + ANSWER_TIMEOUT;
+    while answer.get().is_none() && Instant::now() < deadline {
+        unsafe {
+            let until: Retained<AnyObject> =
+                msg_send![class!(NSDate), dateWithTimeIntervalSinceNow: 0.05f64];
+            let _: () = msg_send![&*run_loop, runUntilDate: &*until];
+        }
+    }
+    match answer.get() {
+        Some(true) => Ok(OpenStatus::launched()),
+        Some(false) => Err(Error::new(
+            ErrorKind::NotFound,
+            format!("no app could open {}", url),
+        )),
+        None => {
+            // UIKit took the URL, so it's as good as handed off
+            log::debug!("no answer from UIKit about opening {} in time", url);
+            Ok(OpenStatus::launched())
+        }
+    }
+}
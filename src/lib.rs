@@ -6,16 +6,29 @@
 //!
 //! * macos => default, as well as browsers listed under [Browser](enum.Browser.html)
 //! * windows => default browser only
-//! * linux or *bsd => default browser only (uses $BROWSER env var, failing back to xdg-open, gvfs-open and
-//! gnome-open, in that order)
+//! * linux or *bsd => default browser (uses $BROWSER env var, failing back to xdg-open, handlr, mimeo,
+//!   gvfs-open and gnome-open, in that order), Firefox, Chrome and Opera if installed, as well as a
+//!   text browser via `Browser::Terminal`. Under WSL, the Windows host browser is used by default
+//!   (via `wslview` or `powershell.exe`), and under Crostini on Chrome OS it's the Chrome OS one
+//!   (via `garcon-url-handler`). Extra arguments for browsers which are launched directly
+//!   can be given via the `WEBBROWSER_ARGS` env var, e.g. `--no-sandbox` in containers
 //! * android => default browser only
-//! * ios => not supported right now
+//! * ios => default browser only, via `UIApplication`, which has to be done from the main thread.
+//!   Needs the `ios` feature
 //!
 //! Important note:
 //!
 //! * This library requires availability of browsers and a graphical environment during runtime
 //! * `cargo test` will actually open the browser locally.
 //!
+//! # Testing
+//!
+//! Code which opens URLs can be tested without a browser by setting the `WEBBROWSER_TEST_SINK` env
+//! var to the path of a file. Nothing is launched then, and a line with the URL and the command
+//! which would have opened it (separated by a tab) is appended to the file instead. The command is
+//! left empty if none of the programs tried were installed. Any other error, like asking for a
+//! browser there's no support for, is returned as usual, and nothing is written then.
+//!
 //! # Examples
 //!
 //! ```no_run
@@ -36,6 +49,11 @@ mod android;
 #[cfg(target_os = "android")]
 use android::*;
 
+#[cfg(all(target_os = "ios", feature = "ios"))]
+mod ios;
+#[cfg(all(target_os = "ios", feature = "ios"))]
+use ios::*;
+
 #[cfg(target_os = "macos")]
 mod macos;
 #[cfg(target_os = "macos")]
@@ -58,19 +76,62 @@ mod unix;
 ))]
 use unix::*;
 
+#[cfg(any(
+    target_os = "macos",
+    target_os = "linux",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "haiku"
+))]
+mod flags;
+
+// the backends whose openers choke on data: URLs
+#[cfg(any(
+    windows,
+    target_os = "linux",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "haiku"
+))]
+mod data_url;
+
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
+#[cfg(all(feature = "preflight", not(target_arch = "wasm32")))]
+mod preflight;
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::borrow::Cow;
 use std::default::Default;
+#[cfg(not(target_arch = "wasm32"))]
+use std::fs;
+#[cfg(not(target_arch = "wasm32"))]
+use std::io::Write;
 use std::io::{Error, ErrorKind, Result};
-use std::process::{ExitStatus, Output};
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+#[cfg(not(target_arch = "wasm32"))]
+use std::process::{Child, Output};
 use std::str::FromStr;
+use std::sync::Arc;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Mutex;
+#[cfg(not(target_arch = "wasm32"))]
+use std::thread;
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
 use std::{error, fmt};
 
-#[cfg(target_arch = "wasm32")]
-use web_sys::Window;
-
-#[derive(Debug, Eq, PartialEq, Copy, Clone, Hash)]
+#[derive(Debug, Default, Eq, PartialEq, Copy, Clone, Hash)]
 /// Browser types available
 pub enum Browser {
     ///Operating system's default browser
+    #[default]
     Default,
 
     ///Mozilla Firefox
@@ -90,6 +151,83 @@ pub enum Browser {
 
     ///Haiku's WebPositive
     WebPositive,
+
+    ///A text browser (lynx, w3m etc.) running inline in the current terminal
+    Terminal,
+}
+
+impl Browser {
+    /// Returns what this browser can be asked to do from the command line, so that apps can e.g.
+    /// grey out an "incognito" checkbox rather than fail when opening. For `Browser::Default`,
+    /// this is worked out from $BROWSER on Linux and *BSD, and is all `false` elsewhere, as
+    /// there's no telling which browser the system will hand the URL over to.
+    ///
+    /// # Examples
+    /// ```
+    /// use webbrowser::Browser;
+    ///
+    /// assert!(Browser::Chrome.capabilities().app_mode);
+    /// assert!(!Browser::Firefox.capabilities().app_mode);
+    /// ```
+    pub fn capabilities(&self) -> BrowserCapabilities {
+        match *self {
+            Browser::Default => default_capabilities(),
+            Browser::Firefox => FIREFOX_CAPABILITIES,
+            Browser::Chrome => CHROMIUM_CAPABILITIES,
+            Browser::Opera => OPERA_CAPABILITIES,
+            _ => BrowserCapabilities::default(),
+        }
+    }
+}
+
+/// Options supported by a browser, as returned by [Browser::capabilities](enum.Browser.html#method.capabilities)
+#[derive(Debug, Default, Eq, PartialEq, Copy, Clone, Hash)]
+pub struct BrowserCapabilities {
+    /// Can open a private/incognito window
+    pub incognito: bool,
+    /// Can open the URL in a new window, rather than a tab
+    pub new_window: bool,
+    /// Can open the URL as a standalone app window, without the browser UI
+    pub app_mode: bool,
+    /// Can run with a specific user profile
+    pub profile: bool,
+    /// Can run fullscreen in kiosk mode
+    pub kiosk: bool,
+}
+
+pub(crate) const FIREFOX_CAPABILITIES: BrowserCapabilities = BrowserCapabilities {
+    incognito: true,
+    new_window: true,
+    app_mode: false,
+    profile: true,
+    kiosk: true,
+};
+
+pub(crate) const CHROMIUM_CAPABILITIES: BrowserCapabilities = BrowserCapabilities {
+    incognito: true,
+    new_window: true,
+    app_mode: true,
+    profile: true,
+    kiosk: true,
+};
+
+pub(crate) const OPERA_CAPABILITIES: BrowserCapabilities = BrowserCapabilities {
+    incognito: true,
+    new_window: true,
+    app_mode: false,
+    profile: true,
+    kiosk: false,
+};
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "haiku"
+)))]
+fn default_capabilities() -> BrowserCapabilities {
+    BrowserCapabilities::default()
 }
 
 ///The Error type for parsing a string into a Browser.
@@ -108,12 +246,47 @@ impl error::Error for ParseBrowserError {
     }
 }
 
-impl Default for Browser {
-    fn default() -> Self {
-        Browser::Default
+/// The error inside the `ErrorKind::NotFound` error returned when a browser can't be used on this
+/// platform at all (e.g. Internet Explorer on Linux), telling it apart from a browser which just
+/// isn't installed.
+///
+/// # Examples
+/// ```no_run
+/// use webbrowser::{open_browser, Browser, UnsupportedBrowserError};
+///
+/// if let Err(e) = open_browser(Browser::InternetExplorer, "http://github.com") {
+///     match e.get_ref().and_then(|e| e.downcast_ref::<UnsupportedBrowserError>()) {
+///         Some(e) => println!("{} can't be used here", e.browser()),
+///         None => println!("couldn't open the browser: {}", e),
+///     }
+/// }
+/// ```
+#[derive(Debug, Eq, PartialEq, Copy, Clone, Hash)]
+pub struct UnsupportedBrowserError {
+    browser: Browser,
+}
+
+impl UnsupportedBrowserError {
+    /// Returns the browser which was asked for
+    pub fn browser(&self) -> Browser {
+        self.browser
     }
 }
 
+impl fmt::Display for UnsupportedBrowserError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} is not available on this platform", self.browser)
+    }
+}
+
+impl error::Error for UnsupportedBrowserError {}
+
+/// Returns the error for `browser` not being usable on this platform
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn unsupported_browser(browser: Browser) -> Error {
+    Error::new(ErrorKind::NotFound, UnsupportedBrowserError { browser })
+}
+
 impl fmt::Display for Browser {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
@@ -124,6 +297,7 @@ impl fmt::Display for Browser {
             Browser::Opera => f.write_str("Opera"),
             Browser::Safari => f.write_str("Safari"),
             Browser::WebPositive => f.write_str("WebPositive"),
+            Browser::Terminal => f.write_str("Terminal"),
         }
     }
 }
@@ -140,11 +314,509 @@ impl FromStr for Browser {
             "opera" => Ok(Browser::Opera),
             "safari" => Ok(Browser::Safari),
             "webpositive" => Ok(Browser::WebPositive),
+            "terminal" => Ok(Browser::Terminal),
             _ => Err(ParseBrowserError),
         }
     }
 }
 
+/// Names accepted when parsing a [Browser](enum.Browser.html) from a string
+#[cfg(not(target_arch = "wasm32"))]
+const BROWSER_NAMES: &[&str] = &[
+    "default",
+    "firefox",
+    "ie",
+    "chrome",
+    "opera",
+    "safari",
+    "webpositive",
+    "terminal",
+];
+
+/// Default cap on the length of a URL (in bytes), as applied by [BrowserOptions](struct.BrowserOptions.html)
+pub const DEFAULT_MAX_URL_LEN: usize = 32 * 1024;
+
+/// Options to tweak how a URL gets opened. Used with
+/// [open_browser_with_options](fn.open_browser_with_options.html).
+///
+/// # Examples
+/// ```no_run
+/// use webbrowser::{open_browser_with_options, Browser, BrowserOptions};
+///
+/// let mut options = BrowserOptions::new();
+/// options.max_url_len(2048);
+/// if open_browser_with_options(Browser::Default, "http://github.com", &options).is_ok() {
+///     // ...
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct BrowserOptions {
+    max_url_len: usize,
+    url_via_stdin: bool,
+    new_tab: bool,
+    background: bool,
+    proxy: Option<String>,
+    lenient_url_validation: bool,
+    incognito: bool,
+    dry_run: bool,
+    dry_run_sink: Option<Callback<DryRunSink>>,
+    prefer_host_browser: bool,
+    force_tab_in_running: Option<bool>,
+    locale: Option<String>,
+    systemd_scope: bool,
+    url_transform: Option<Callback<UrlTransform>>,
+    fallback_to_default: bool,
+    wrapper: Option<Vec<String>>,
+    new_window: bool,
+    inter_open_delay: Duration,
+    treat_paths_as_files: bool,
+    raw: bool,
+    #[cfg(feature = "preflight")]
+    preflight: bool,
+    screen_hint: Option<usize>,
+    screen_origins: Vec<(i32, i32)>,
+    blocking: bool,
+    strict: bool,
+    inherit_proxy_env: bool,
+}
+
+/// Receives the program and arguments of each command tried in dry-run mode
+type DryRunSink = dyn Fn(&str, &[String]) + Send + Sync;
+
+/// Rewrites a URL before it's opened
+type UrlTransform = dyn Fn(&str) -> String + Send + Sync;
+
+/// Wraps a user-supplied function, so that [BrowserOptions] can still be cloned and debugged
+struct Callback<F: ?Sized>(Arc<F>);
+
+impl<F: ?Sized> Clone for Callback<F> {
+    fn clone(&self) -> Self {
+        Callback(self.0.clone())
+    }
+}
+
+impl<F: ?Sized> fmt::Debug for Callback<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Callback")
+    }
+}
+
+impl BrowserOptions {
+    /// Creates the default set of options
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum length (in bytes) of a URL that will be handed to a browser. Longer URLs
+    /// are rejected with `ErrorKind::InvalidInput` before anything gets spawned, which keeps
+    /// user-supplied input from blowing past command-line limits. Defaults to
+    /// [DEFAULT_MAX_URL_LEN](constant.DEFAULT_MAX_URL_LEN.html); pass `usize::MAX` to disable
+    /// the check.
+    pub fn max_url_len(&mut self, len: usize) -> &mut Self {
+        self.max_url_len = len;
+        self
+    }
+
+    /// When set, the URL is written to the standard input of custom openers (the commands listed
+    /// in $BROWSER on Linux and *BSD) instead of being appended to their arguments, for wrapper
+    /// scripts which expect `echo url | opener`. Defaults to `false`.
+    pub fn url_via_stdin(&mut self, enable: bool) -> &mut Self {
+        self.url_via_stdin = enable;
+        self
+    }
+
+    /// When set, the URL is opened in a new tab of the browser's front window, where the platform
+    /// supports such control (currently Safari and Chrome on macOS). Defaults to `false`.
+    pub fn new_tab(&mut self, enable: bool) -> &mut Self {
+        self.new_tab = enable;
+        self
+    }
+
+    /// When set, the URL is opened in a new window, rather than in a tab of an existing one. This
+    /// is done by passing `-new-window` to Firefox, and `--new-window` to Chromium-family
+    /// browsers, when they're launched directly (including via $BROWSER). Along with
+    /// [incognito](#method.incognito), Firefox only gets `-private-window`, which always opens a
+    /// new window anyway. Ignored for browsers which are opened via the system. Defaults to
+    /// `false`.
+    pub fn new_window(&mut self, enable: bool) -> &mut Self {
+        self.new_window = enable;
+        self
+    }
+
+    /// When set, the URL is opened without bringing the browser to the foreground, where the
+    /// platform supports it (currently macOS). Defaults to `false`.
+    pub fn background(&mut self, enable: bool) -> &mut Self {
+        self.background = enable;
+        self
+    }
+
+    /// Routes the browser through the given proxy (`host:port`), by passing `--proxy-server` to
+    /// Chromium-family browsers when they are launched directly. Firefox has no command line flag
+    /// for this, so it needs a profile with the proxy pre-configured instead, and the option is
+    /// ignored for it (as well as for the default browser).
+    pub fn proxy(&mut self, proxy: impl Into<String>) -> &mut Self {
+        self.proxy = Some(proxy.into());
+        self
+    }
+
+    /// When set, and no [proxy](#method.proxy) is given, the proxy is taken from the
+    /// `HTTP_PROXY`/`HTTPS_PROXY` env vars (or their lowercase versions), along with the hosts in
+    /// `NO_PROXY` to bypass it for, as is usual in containers. Like an explicit proxy, this only
+    /// reaches Chromium-family browsers, which don't look at those vars themselves. Other browsers
+    /// (like Firefox, with its default system proxy settings) go by the environment they inherit,
+    /// if at all. Defaults to `false`.
+    pub fn inherit_proxy_env(&mut self, enable: bool) -> &mut Self {
+        self.inherit_proxy_env = enable;
+        self
+    }
+
+    /// URLs containing characters which RFC 3986 doesn't allow unencoded (control characters,
+    /// spaces, and any of ``"<>\^`{|}``) are rejected with `ErrorKind::InvalidInput` by default, as
+    /// they can be mangled on their way into a command line. When this is set, such URLs only log
+    /// a warning and are opened anyway. Non-ASCII characters are always allowed, so that
+    /// international domain names and paths work. Defaults to `false`.
+    pub fn lenient_url_validation(&mut self, enable: bool) -> &mut Self {
+        self.lenient_url_validation = enable;
+        self
+    }
+
+    /// When set, the URL is opened in a private/incognito window. The right flag is picked based
+    /// on the name of the command which launches the browser, so this also works for browsers
+    /// given via $BROWSER. Opening fails with `ErrorKind::Unsupported` if the browser isn't one we
+    /// know the flag for, which includes the default browser unless $BROWSER is set on Linux and
+    /// *BSD. Defaults to `false`.
+    pub fn incognito(&mut self, enable: bool) -> &mut Self {
+        self.incognito = enable;
+        self
+    }
+
+    /// When set, nothing gets launched. Instead, each command the crate would try is reported (to
+    /// the [dry_run_sink](#method.dry_run_sink) if one is set, or else logged), in order, and the
+    /// first one which is available is treated as having succeeded. Defaults to `false`.
+    pub fn dry_run(&mut self, enable: bool) -> &mut Self {
+        self.dry_run = enable;
+        self
+    }
+
+    /// Sets the function which receives the program and arguments of each command tried in
+    /// [dry_run](#method.dry_run) mode
+    ///
+    /// # Examples
+    /// ```
+    /// use std::sync::{Arc, Mutex};
+    /// use webbrowser::{open_browser_with_options, Browser, BrowserOptions};
+    ///
+    /// let tried = Arc::new(Mutex::new(Vec::new()));
+    /// let sink = tried.clone();
+    /// let mut options = BrowserOptions::new();
+    /// options
+    ///     .dry_run(true)
+    ///     .dry_run_sink(move |program, args| sink.lock().unwrap().push((program.to_string(), args.to_vec())));
+    /// let _ = open_browser_with_options(Browser::Default, "http://github.com", &options);
+    /// println!("would have tried {:?}", tried.lock().unwrap());
+    /// ```
+    pub fn dry_run_sink<F>(&mut self, sink: F) -> &mut Self
+    where
+        F: Fn(&str, &[String]) + Send + Sync + 'static,
+    {
+        self.dry_run_sink = Some(Callback(Arc::new(sink)));
+        self
+    }
+
+    /// Whether browsers which are launched directly should be passed the flags which hand the URL
+    /// over to an already running instance as a new tab (`-new-tab` for Firefox), rather than
+    /// starting another one. Chromium-family browsers do this on their own, so nothing extra is
+    /// passed to them. By default, the flags are only passed if the browser is found to be
+    /// running already (on Linux and *BSD), so that a fresh launch doesn't get a `-new-tab` into
+    /// nothing. Setting this overrides that check either way.
+    pub fn force_tab_in_running(&mut self, enable: bool) -> &mut Self {
+        self.force_tab_in_running = Some(enable);
+        self
+    }
+
+    /// Opens the browser with its UI in the given language (e.g. `de_DE.UTF-8`), regardless of the
+    /// system locale. On Linux and *BSD, `LANG` and `LC_ALL` are set for the launched process
+    /// (which is what Firefox goes by), and Chromium-family browsers are also passed the matching
+    /// `--lang` flag when they're launched directly, which is all that macOS gets. The environment
+    /// of the calling process is left alone. A browser which is running already keeps its
+    /// language.
+    pub fn locale(&mut self, locale: impl Into<String>) -> &mut Self {
+        self.locale = Some(locale.into());
+        self
+    }
+
+    /// On Linux desktops running systemd, launches the browser (or the opener) via `systemd-run
+    /// --user --scope`, so that it gets a transient scope of its own, the way desktop environments
+    /// launch apps. Otherwise, a browser launched from a short-lived service ends up in the
+    /// service's cgroup, and gets killed along with it. Falls back to a plain launch if
+    /// `systemd-run` isn't installed. Defaults to `false`.
+    pub fn systemd_scope(&mut self, enable: bool) -> &mut Self {
+        self.systemd_scope = enable;
+        self
+    }
+
+    /// When set, a URL which can't be opened in the requested browser because it can't be found
+    /// (e.g. Chrome isn't installed) is opened in the default browser instead, with a warning
+    /// logged. [open_browser_outcome](fn.open_browser_outcome.html) tells if this happened, so
+    /// that the user can be told. Defaults to `false`, i.e. failing with `ErrorKind::NotFound`.
+    pub fn fallback_to_default(&mut self, enable: bool) -> &mut Self {
+        self.fallback_to_default = enable;
+        self
+    }
+
+    /// Sets a command to prefix the browser's own command with, e.g. `["firejail"]` to run it in a
+    /// sandbox, or `["sudo", "-u", "browseruser"]` to run it as a less privileged user. This only
+    /// applies when the browser is launched directly (on Linux and *BSD, for named browsers and
+    /// browsers set in $BROWSER), not when the URL is handed off to an opener like `xdg-open`.
+    ///
+    /// The wrapper is run with the privileges of the calling process, and gets the URL as an
+    /// argument, so it should only ever come from trusted configuration. Note that a wrapper which
+    /// grants privileges (like `sudo` without `-u`) defeats the purpose, as the browser would then
+    /// run with more of them, not fewer.
+    pub fn wrapper(&mut self, wrapper: Vec<String>) -> &mut Self {
+        self.wrapper = Some(wrapper);
+        self
+    }
+
+    /// Sets how long [open_multiple](fn.open_multiple.html) waits between opening one URL and the
+    /// next, as some browsers drop tabs when too many arrive at once (especially while still
+    /// starting up). Defaults to zero, i.e. no waiting.
+    pub fn inter_open_delay(&mut self, delay: Duration) -> &mut Self {
+        self.inter_open_delay = delay;
+        self
+    }
+
+    /// When set, what's passed in for a URL is first checked for being the path (absolute, or
+    /// relative to the current dir) of an existing file or dir, and if so, it's opened the same
+    /// way as via [open_path](fn.open_path.html). Anything with a scheme is always taken to be a
+    /// URL. Defaults to `false`, as a schemeless host name (`example.com`) could then end up being
+    /// opened as a file, just because one by that name happens to exist.
+    pub fn treat_paths_as_files(&mut self, enable: bool) -> &mut Self {
+        self.treat_paths_as_files = enable;
+        self
+    }
+
+    /// When set, the URL is handed to the opener exactly as given, for callers who've prepared it
+    /// themselves and need it to get through untouched. None of the usual checks and conversions
+    /// are done then: it's not validated (not even for its length), data: URLs and paths aren't
+    /// turned into files, the [url_transform](#method.url_transform) isn't applied, and its scheme
+    /// doesn't decide which opener gets it. Defaults to `false`.
+    ///
+    /// This means there's also nothing stopping a `javascript:` or `file:` URL, or one with
+    /// control characters or arguments-looking text in it, from reaching the opener, so raw mode
+    /// should only ever be used with URLs which don't come from untrusted input.
+    pub fn raw(&mut self, enable: bool) -> &mut Self {
+        self.raw = enable;
+        self
+    }
+
+    /// When set, the server behind an http(s) URL is checked for being up before the browser is
+    /// opened on it, so that the user can be told the site is down, rather than be shown the
+    /// browser's error page. http URLs get a `HEAD` request, which the server has to answer with
+    /// anything but a server error (5xx), while for https ones the server accepting the connection
+    /// has to do. Either has 3 seconds to happen. If the check fails, the browser isn't opened,
+    /// and the error says the URL is unreachable, with the `ErrorKind` of what went wrong (e.g.
+    /// `ConnectionRefused` or `TimedOut`). Defaults to `false`.
+    ///
+    /// Only available with the `preflight` feature.
+    #[cfg(feature = "preflight")]
+    pub fn preflight(&mut self, enable: bool) -> &mut Self {
+        self.preflight = enable;
+        self
+    }
+
+    /// Asks for the browser window to be put on the given screen (counting from 0) of a
+    /// multi-monitor setup, whose layout has to be given via
+    /// [screen_origins](#method.screen_origins). This is best-effort: only Chromium-family
+    /// browsers can be told where to put their window (via `--window-position`), and only when
+    /// they're launched directly, as a running instance opens new windows wherever it likes.
+    /// Other browsers have no way of being told, and the hint is ignored for them.
+    ///
+    /// `--window-position` is the only thing the hint turns into. No environment hint is passed,
+    /// as neither X11 nor Wayland has one which browsers go by to pick a monitor (an X `DISPLAY`
+    /// screen like `:0.1` is a separate screen altogether, not a monitor of the same desktop).
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use webbrowser::{open_browser_with_options, Browser, BrowserOptions};
+    ///
+    /// // two 1920x1080 monitors side by side, opening on the right one
+    /// let mut options = BrowserOptions::new();
+    /// options.screen_origins(vec![(0, 0), (1920, 0)]).screen_hint(1);
+    /// let _ = open_browser_with_options(Browser::Chrome, "http://localhost:8000", &options);
+    /// ```
+    pub fn screen_hint(&mut self, screen: usize) -> &mut Self {
+        self.screen_hint = Some(screen);
+        self
+    }
+
+    /// Sets the position of the top-left corner of each screen, in the coordinates of the whole
+    /// desktop, for the [screen_hint](#method.screen_hint) to go by
+    pub fn screen_origins(&mut self, origins: Vec<(i32, i32)>) -> &mut Self {
+        self.screen_origins = origins;
+        self
+    }
+
+    /// Sets whether to wait for the opener to exit, on Linux and *BSD. This applies alike to
+    /// $BROWSER and to the generic openers like `xdg-open`, whichever ends up doing the job. When
+    /// set (the default), the call returns once the opener does, and its exit status is reported.
+    /// Note that this can take as long as the browser stays open, if the opener is the browser
+    /// itself, and no instance of it was running yet. When not set, the opener is only launched,
+    /// and the call returns straight away, without knowing how it went.
+    ///
+    /// Text browsers always run to completion, as they take over the terminal, as do openers which
+    /// get the url via [url_via_stdin](#method.url_via_stdin). Browsers which are launched
+    /// directly (e.g. `Browser::Firefox`) are never waited for.
+    pub fn blocking(&mut self, enable: bool) -> &mut Self {
+        self.blocking = enable;
+        self
+    }
+
+    /// Sets whether to stop at the first opener which is installed, but doesn't work out, on Linux
+    /// and *BSD. By default, every opener in the chain ($BROWSER, then `xdg-open` and the like) is
+    /// tried until one succeeds, and if none does, the exit status of the first one which ran is
+    /// reported. That's the most likely way of getting the URL opened, but it's also how a real
+    /// problem (a browser which can't be run for lack of permission, or which crashes on startup)
+    /// can go unnoticed, by being covered up by a later opener, or by the summary of all that was
+    /// tried. In strict mode, such an opener is the last one tried, and how it failed (its error,
+    /// or exit status) is what's returned. Openers which aren't installed are skipped either way.
+    /// Defaults to `false`.
+    pub fn strict(&mut self, enable: bool) -> &mut Self {
+        self.strict = enable;
+        self
+    }
+
+    /// Under WSL, where both the Windows host browser and Linux GUI apps (via WSLg) can be reached,
+    /// picks which of the two the default browser should be. When set, the url is handed to the
+    /// host via `wslview` or `powershell.exe` first, falling back to the Linux openers, and the
+    /// other way round when unset. $BROWSER is always tried first either way. Has no effect
+    /// outside WSL. Defaults to `true`, as that's where most users want their web links to go.
+    pub fn prefer_host_browser(&mut self, enable: bool) -> &mut Self {
+        self.prefer_host_browser = enable;
+        self
+    }
+
+    /// Sets a function through which every URL is passed before it's opened, e.g. to point
+    /// internal hosts at a gateway, or to add query parameters. The transform is applied first,
+    /// and it's the transformed URL which is then validated (as per
+    /// [max_url_len](#method.max_url_len) etc.), handed to the browser, and reported in
+    /// [dry_run](#method.dry_run) mode.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use webbrowser::{open_browser_with_options, Browser, BrowserOptions};
+    ///
+    /// let mut options = BrowserOptions::new();
+    /// options.url_transform(|url| url.replace("http://internal/", "https://gateway.example.com/"));
+    /// let _ = open_browser_with_options(Browser::Default, "http://internal/wiki", &options);
+    /// ```
+    pub fn url_transform<F>(&mut self, transform: F) -> &mut Self
+    where
+        F: Fn(&str) -> String + Send + Sync + 'static,
+    {
+        self.url_transform = Some(Callback(Arc::new(transform)));
+        self
+    }
+
+    /// Reports a command tried in dry-run mode
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn report_dry_run(&self, program: &str, args: &[String]) {
+        match self.dry_run_sink {
+            Some(Callback(ref sink)) => sink(program, args),
+            None => log::info!("dry run: {} {:?}", program, args),
+        }
+    }
+
+    /// Reports `cmd` if in dry-run mode, and otherwise runs it to completion
+    #[cfg(any(target_os = "macos", target_os = "android"))]
+    pub(crate) fn run(&self, cmd: &mut std::process::Command) -> Result<OpenStatus> {
+        if self.dry_run {
+            let (program, args) = command_line(cmd);
+            self.report_dry_run(&program, &args);
+            Ok(OpenStatus::launched())
+        } else {
+            cmd.status().map(OpenStatus::from)
+        }
+    }
+}
+
+impl Default for BrowserOptions {
+    fn default() -> Self {
+        BrowserOptions {
+            max_url_len: DEFAULT_MAX_URL_LEN,
+            url_via_stdin: false,
+            new_tab: false,
+            background: false,
+            proxy: None,
+            lenient_url_validation: false,
+            incognito: false,
+            dry_run: false,
+            dry_run_sink: None,
+            prefer_host_browser: true,
+            force_tab_in_running: None,
+            locale: None,
+            systemd_scope: false,
+            url_transform: None,
+            fallback_to_default: false,
+            wrapper: None,
+            new_window: false,
+            inter_open_delay: Duration::from_secs(0),
+            treat_paths_as_files: false,
+            raw: false,
+            #[cfg(feature = "preflight")]
+            preflight: false,
+            screen_hint: None,
+            screen_origins: Vec::new(),
+            blocking: true,
+            strict: false,
+            inherit_proxy_env: false,
+        }
+    }
+}
+
+/// Returns the program and arguments of `cmd` as strings
+#[cfg(not(any(windows, target_os = "ios", target_arch = "wasm32")))]
+pub(crate) fn command_line(cmd: &std::process::Command) -> (String, Vec<String>) {
+    (
+        cmd.get_program().to_string_lossy().into_owned(),
+        cmd.get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect(),
+    )
+}
+
+/// Checks the URL against the sanity limits configured in `options`
+fn validate_url(url: &str, options: &BrowserOptions) -> Result<()> {
+    if url.len() > options.max_url_len {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "url is {} bytes long, exceeding the limit of {} bytes",
+                url.len(),
+                options.max_url_len
+            ),
+        ));
+    }
+    if let Some(c) = url.chars().find(|&c| is_disallowed_in_url(c)) {
+        let msg = format!("url contains disallowed character {:?}", c);
+        if options.lenient_url_validation {
+            log::warn!("{}: {}", msg, url);
+        } else {
+            return Err(Error::new(ErrorKind::InvalidInput, msg));
+        }
+    }
+    Ok(())
+}
+
+/// Returns true if `c` can't appear unencoded anywhere in a URL, per RFC 3986
+fn is_disallowed_in_url(c: char) -> bool {
+    c.is_ascii_control()
+        || matches!(
+            c,
+            ' ' | '"' | '<' | '>' | '\\' | '^' | '`' | '{' | '|' | '}'
+        )
+}
+
 /// Opens the URL on the default browser of this platform
 ///
 /// Returns Ok(..) so long as the browser invocation was successful. An Err(..) is returned only if
@@ -172,16 +844,14 @@ pub fn open(url: &str) -> Result<Output> {
 
 #[cfg(target_arch = "wasm32")]
 pub fn open(url: &str) -> Result<()> {
+    validate_url(url, &BrowserOptions::default())?;
     let window = web_sys::window();
     match window {
-        Some(w) => {
-            w.open_with_url(url);
-            Ok(())
-        }
-        None => Err(std::io::Error::new(
-            ErrorKind::Other,
-            "should have a window in this context",
-        )),
+        Some(w) => w
+            .open_with_url(url)
+            .map(|_| ())
+            .map_err(|_| Error::other(format!("the window refused to open {}", url))),
+        None => Err(Error::other("should have a window in this context")),
     }
 }
 
@@ -198,65 +868,1621 @@ pub fn open(url: &str) -> Result<()> {
 /// ```
 #[cfg(not(target_arch = "wasm32"))]
 pub fn open_browser(browser: Browser, url: &str) -> Result<Output> {
-    open_browser_internal(browser, url).and_then(|status| {
-        if let Some(code) = status.code() {
-            if code == 0 {
-                Ok(Output {
-                    status: ExitStatus::from_raw(0),
-                    stdout: vec![],
-                    stderr: vec![],
-                })
-            } else {
-                Err(Error::new(
-                    ErrorKind::Other,
-                    format!("return code {}", code),
-                ))
-            }
-        } else {
-            Err(Error::new(ErrorKind::Other, "interrupted by signal"))
-        }
+    open_browser_with_options(browser, url, &BrowserOptions::default())
+}
+
+/// Opens the specified URL on the browser identified by `browser_name`, for callers which would
+/// rather not deal with the [Browser](enum.Browser.html) enum. The name is parsed case-insensitively
+/// the same way as `Browser::from_str`, and an unknown name results in an `ErrorKind::InvalidInput`
+/// error listing the supported ones. Return semantics are otherwise the same as for
+/// [open](fn.open.html).
+///
+/// # Examples
+/// ```no_run
+/// use webbrowser::open_in;
+///
+/// if open_in("firefox", "http://github.com").is_ok() {
+///     // ...
+/// }
+/// ```
+#[cfg(not(target_arch = "wasm32"))]
+pub fn open_in(browser_name: &str, url: &str) -> Result<Output> {
+    open_browser(parse_browser_name(browser_name)?, url)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn parse_browser_name(browser_name: &str) -> Result<Browser> {
+    browser_name.to_lowercase().parse().map_err(|_| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "unknown browser {:?}, expected one of: {}",
+                browser_name,
+                BROWSER_NAMES.join(", ")
+            ),
+        )
     })
 }
 
-#[cfg(not(any(
-    target_os = "android",
-    target_os = "windows",
-    target_os = "macos",
-    target_os = "linux",
-    target_os = "freebsd",
-    target_os = "netbsd",
-    target_os = "openbsd",
-    target_os = "haiku",
-    target_arch = "wasm32"
-)))]
-compile_error!("Only Windows, Mac OS, Linux, *BSD and Haiku and Wasm32 are currently supported");
+/// Opens a local file on the default browser of this platform, by turning `path` into a `file://`
+/// URL. Relative paths are resolved against the current directory. Return semantics are the same
+/// as for [open](fn.open.html).
+///
+/// # Examples
+/// ```no_run
+/// use webbrowser;
+///
+/// if webbrowser::open_path("target/doc/webbrowser/index.html").is_ok() {
+///     // ...
+/// }
+/// ```
+#[cfg(not(target_arch = "wasm32"))]
+pub fn open_path<P: AsRef<Path>>(path: P) -> Result<Output> {
+    open(&path_to_url(path.as_ref())?)
+}
 
-#[test]
-#[ignore]
-fn test_open_firefox() {
-    assert!(open_browser(Browser::Firefox, "http://github.com").is_ok());
+/// Converts the path into a `file://` URL, making it absolute first
+#[cfg(not(target_arch = "wasm32"))]
+fn path_to_url(path: &Path) -> Result<String> {
+    if path.is_absolute() {
+        file_url_internal(path)
+    } else {
+        file_url_internal(&std::env::current_dir()?.join(path))
+    }
 }
 
-#[test]
-#[ignore]
-fn test_open_chrome() {
-    assert!(open_browser(Browser::Chrome, "http://github.com").is_ok());
+/// Converts an absolute path into a `file://` URL
+#[cfg(not(any(windows, target_arch = "wasm32")))]
+fn file_url_internal(path: &Path) -> Result<String> {
+    let path = path
+        .to_str()
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "path is not valid unicode"))?;
+    Ok(format!("file://{}", encode_url_path(path)))
 }
 
-#[test]
-#[cfg(target_arch = "wasm32")]
-fn test_open_default_wasm() {
-    assert!(open("http://github.com").is_ok());
+/// Percent-encodes everything in `path` which can't appear as-is in the path of a URL, leaving the
+/// `/` separators alone
+#[cfg(not(target_arch = "wasm32"))]
+fn encode_url_path(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for b in path.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' => encoded.push(b as char),
+            b'-' | b'.' | b'_' | b'~' | b'/' | b':' | b'@' | b'!' | b'$' | b'&' | b'\'' => {
+                encoded.push(b as char)
+            }
+            b'(' | b')' | b'*' | b'+' | b',' | b';' | b'=' => encoded.push(b as char),
+            _ => encoded.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    encoded
 }
 
-#[test]
-#[ignore]
-fn test_open_safari() {
-    assert!(open_browser(Browser::Safari, "http://github.com").is_ok());
+/// Opens the specified URL on the specific browser (if available) requested, while honouring the
+/// supplied [BrowserOptions](struct.BrowserOptions.html). Return semantics are the same as for
+/// [open](fn.open.html).
+///
+/// # Examples
+/// ```no_run
+/// use webbrowser::{open_browser_with_options, Browser, BrowserOptions};
+///
+/// let url = "http://github.com";
+/// if open_browser_with_options(Browser::Default, url, BrowserOptions::new().max_url_len(1024)).is_ok() {
+///     // ...
+/// }
+/// ```
+#[cfg(not(target_arch = "wasm32"))]
+pub fn open_browser_with_options(
+    browser: Browser,
+    url: &str,
+    options: &BrowserOptions,
+) -> Result<Output> {
+    open_browser_status(browser, url, options)
+        .and_then(OpenStatus::into_result)
+        .map(|_| Output {
+            status: ExitStatus::from_raw(0),
+            stdout: vec![],
+            stderr: vec![],
+        })
 }
 
-#[test]
-#[ignore]
-fn test_open_webpositive() {
-    assert!(open_browser(Browser::WebPositive, "http://github.com").is_ok());
+/// Opens the specified URL on the specific browser (if available) requested, and returns the
+/// [OpenStatus](struct.OpenStatus.html) of the launch. Unlike [open_browser](fn.open_browser.html),
+/// an opener which ran but failed is not turned into an Err(..), so the status can be inspected the
+/// same way on every platform. An Err(..) is returned only if no opener could be run at all.
+///
+/// # Examples
+/// ```no_run
+/// use webbrowser::{open_browser_status, Browser, BrowserOptions};
+///
+/// match open_browser_status(Browser::Default, "http://github.com", &BrowserOptions::new()) {
+///     Ok(status) if status.success() => println!("opened"),
+///     Ok(status) => println!("opener failed with {:?}", status.code()),
+///     Err(e) => println!("no opener: {}", e),
+/// }
+/// ```
+#[cfg(not(target_arch = "wasm32"))]
+pub fn open_browser_status(
+    browser: Browser,
+    url: &str,
+    options: &BrowserOptions,
+) -> Result<OpenStatus> {
+    open_browser_outcome(browser, url, options).map(|outcome| outcome.status())
+}
+
+/// Same as [open_browser_status](fn.open_browser_status.html), but also telling which browser the
+/// URL was opened in, which can differ from the requested one if
+/// [fallback_to_default](struct.BrowserOptions.html#method.fallback_to_default) is set.
+///
+/// # Examples
+/// ```no_run
+/// use webbrowser::{open_browser_outcome, Browser, BrowserOptions};
+///
+/// let mut options = BrowserOptions::new();
+/// options.fallback_to_default(true);
+/// let outcome = open_browser_outcome(Browser::Chrome, "http://github.com", &options).unwrap();
+/// if outcome.fell_back() {
+///     println!("Chrome isn't installed, so it was opened in your default browser");
+/// }
+/// ```
+#[cfg(not(target_arch = "wasm32"))]
+pub fn open_browser_outcome(
+    browser: Browser,
+    url: &str,
+    options: &BrowserOptions,
+) -> Result<OpenOutcome> {
+    let url = prepare_url(url, options)?;
+    let browser = dispatch_browser(browser, &url, options);
+    if let Some(sink) = test_sink() {
+        return open_into_test_sink(&sink, browser, &url, options);
+    }
+    open_with_fallback(browser, options, |browser| {
+        open_browser_internal(browser, &url, options)
+    })
+}
+
+/// Opens the URL with the installed application whose [desktop entry](https://specifications.freedesktop.org/desktop-entry-spec/latest/)
+/// id is `desktop_id` (e.g. `firefox.desktop`, the suffix being optional), honouring the flags the
+/// entry launches it with. This is done via `gtk-launch` if it's installed, or else by running the
+/// `Exec` command of the entry. An `ErrorKind::NotFound` error is returned if there's no such entry.
+///
+/// # Examples
+/// ```no_run
+/// use webbrowser::open_desktop_app;
+///
+/// if open_desktop_app("org.mozilla.firefox.desktop", "http://github.com").is_ok() {
+///     // ...
+/// }
+/// ```
+#[cfg(any(
+    target_os = "linux",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "haiku"
+))]
+pub fn open_desktop_app(desktop_id: &str, url: &str) -> Result<Output> {
+    let options = BrowserOptions::default();
+    let url = prepare_url(url, &options)?;
+    match test_sink() {
+        Some(sink) => {
+            record_in_test_sink(&sink, &url, &options, OpenStatus::launched(), |options| {
+                open_desktop_app_internal(desktop_id, &url, options)
+            })
+        }
+        None => open_desktop_app_internal(desktop_id, &url, &options),
+    }
+    .and_then(OpenStatus::into_result)
+    .map(|_| Output {
+        status: ExitStatus::from_raw(0),
+        stdout: vec![],
+        stderr: vec![],
+    })
+}
+
+/// Opens the URL by running the command given by `template`, the same way as an entry of the
+/// `$BROWSER` env var would be, but without having to set it. The template is split up into words
+/// with shell-like quoting (though nothing else of the shell applies), and `%s` or `%u` are
+/// replaced by the URL, which is otherwise appended as the last argument. Return semantics are the
+/// same as for [open](fn.open.html).
+///
+/// # Examples
+/// ```no_run
+/// use webbrowser::open_with_template;
+///
+/// if open_with_template("myproxy open --url %s --flag", "http://github.com").is_ok() {
+///     // ...
+/// }
+/// ```
+#[cfg(any(
+    target_os = "linux",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "haiku"
+))]
+pub fn open_with_template(template: &str, url: &str) -> Result<Output> {
+    let options = BrowserOptions::default();
+    let url = prepare_url(url, &options)?;
+    match test_sink() {
+        Some(sink) => {
+            record_in_test_sink(&sink, &url, &options, OpenStatus::launched(), |options| {
+                open_with_template_internal(template, &url, options)
+            })
+        }
+        None => open_with_template_internal(template, &url, &options),
+    }
+    .and_then(OpenStatus::into_result)
+    .map(|_| Output {
+        status: ExitStatus::from_raw(0),
+        stdout: vec![],
+        stderr: vec![],
+    })
+}
+
+/// Returns the file set via `WEBBROWSER_TEST_SINK`, to which URLs are written instead of being
+/// opened
+#[cfg(not(target_arch = "wasm32"))]
+fn test_sink() -> Option<PathBuf> {
+    std::env::var_os("WEBBROWSER_TEST_SINK")
+        .filter(|sink| !sink.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Appends `url` to `sink`, along with the command which would have opened it in `browser`
+#[cfg(not(target_arch = "wasm32"))]
+fn open_into_test_sink(
+    sink: &Path,
+    browser: Browser,
+    url: &str,
+    options: &BrowserOptions,
+) -> Result<OpenOutcome> {
+    let launched = OpenOutcome {
+        status: OpenStatus::launched(),
+        browser,
+        fell_back: false,
+    };
+    record_in_test_sink(sink, url, options, launched, |dry_run| {
+        open_with_fallback(browser, dry_run, |browser| {
+            open_browser_internal(browser, url, dry_run)
+        })
+    })
+}
+
+/// Appends `url` to `sink`, along with the command which `open` would have run for it, as found
+/// by calling it in [dry_run](struct.BrowserOptions.html#method.dry_run) mode. Nothing needs to be
+/// installed for the url to reach the sink, so if the commands tried weren't found, `launched` is
+/// returned.
+#[cfg(not(target_arch = "wasm32"))]
+fn record_in_test_sink<T>(
+    sink: &Path,
+    url: &str,
+    options: &BrowserOptions,
+    launched: T,
+    open: impl FnOnce(&BrowserOptions) -> Result<T>,
+) -> Result<T> {
+    let command = Arc::new(Mutex::new(String::new()));
+    let mut dry_run = options.clone();
+    {
+        let command = command.clone();
+        dry_run.dry_run(true).dry_run_sink(move |program, args| {
+            *command.lock().unwrap() = shell_quote_command(program, args)
+        });
+    }
+    let last_tried = || command.lock().unwrap().clone();
+    let (outcome, command) = match open(&dry_run) {
+        // the last command tried is the one which got through
+        Ok(outcome) => (outcome, last_tried()),
+        // only a missing program is let through, not e.g. a browser there's no support for at all
+        Err(ref e) if e.kind() == ErrorKind::NotFound && !last_tried().is_empty() => {
+            (launched, String::new())
+        }
+        Err(e) => return Err(e),
+    };
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(sink)?;
+    writeln!(file, "{}\t{}", url, command)?;
+    Ok(outcome)
+}
+
+/// Opens via `open` in `browser`, retrying with the default browser if `browser` can't be found
+/// and the options allow for it
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn open_with_fallback(
+    browser: Browser,
+    options: &BrowserOptions,
+    mut open: impl FnMut(Browser) -> Result<OpenStatus>,
+) -> Result<OpenOutcome> {
+    match open(browser) {
+        Err(ref e)
+            if e.kind() == ErrorKind::NotFound
+                && options.fallback_to_default
+                && browser != Browser::Default =>
+        {
+            log::warn!("{} not found ({}), using the default browser", browser, e);
+            open(Browser::Default).map(|status| OpenOutcome {
+                status,
+                browser: Browser::Default,
+                fell_back: true,
+            })
+        }
+        result => result.map(|status| OpenOutcome {
+            status,
+            browser,
+            fell_back: false,
+        }),
+    }
+}
+
+/// Returns the browser which should actually open `url`. URLs with schemes other than those a
+/// browser deals with (e.g. `steam:` or `zoommtg:`) are always handed to whatever handler the
+/// system has registered for them, i.e. to the default "browser".
+#[cfg(not(target_arch = "wasm32"))]
+fn dispatch_browser(browser: Browser, url: &str, options: &BrowserOptions) -> Browser {
+    if browser != Browser::Default && !options.raw && has_custom_scheme(url) {
+        log::debug!(
+            "opening {} via the system handler for its scheme, instead of {}",
+            url,
+            browser
+        );
+        Browser::Default
+    } else {
+        browser
+    }
+}
+
+/// Schemes which are for browsers to deal with
+#[cfg(not(target_arch = "wasm32"))]
+const BROWSER_SCHEMES: &[&str] = &["http", "https", "file", "ftp", "about", "data"];
+
+/// Returns the scheme of `url` in lowercase, if it has one. A single letter isn't taken to be a
+/// scheme, so that Windows paths like `C:\...` don't look like one.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn url_scheme(url: &str) -> Option<String> {
+    let (scheme, _) = url.split_once(':')?;
+    let mut chars = scheme.chars();
+    let is_scheme = chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+        && scheme.len() > 1;
+    if is_scheme {
+        Some(scheme.to_ascii_lowercase())
+    } else {
+        None
+    }
+}
+
+/// Returns true if `url` has a scheme, and it isn't one a browser deals with
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn has_custom_scheme(url: &str) -> bool {
+    url_scheme(url).is_some_and(|scheme| !BROWSER_SCHEMES.contains(&scheme.as_str()))
+}
+
+/// Tries every way there is of opening the URL in the requested browser, rather than stopping at
+/// the first one which works, and returns each command along with how it went. This gives a full
+/// picture of the environment, e.g. for a `--doctor` command.
+///
+/// As actually running every opener would open the URL several times over, this is done in
+/// [dry_run](struct.BrowserOptions.html#method.dry_run) mode, where a command counts as having
+/// worked if it's installed. Use
+/// [open_browser_report_with_options](fn.open_browser_report_with_options.html) to run them all
+/// for real. Only Linux and *BSD have more than one way of opening a URL. If the URL itself is
+/// rejected, the report has just the URL, along with the error.
+///
+/// # Examples
+/// ```no_run
+/// use webbrowser::{open_browser_report, Browser};
+///
+/// for (cmd, result) in open_browser_report(Browser::Default, "http://github.com") {
+///     println!("{}: {}", cmd, if result.is_ok() { "ok" } else { "failed" });
+/// }
+/// ```
+#[cfg(not(target_arch = "wasm32"))]
+pub fn open_browser_report(browser: Browser, url: &str) -> Vec<(String, Result<OpenStatus>)> {
+    let mut options = BrowserOptions::new();
+    options.dry_run(true);
+    open_browser_report_with_options(browser, url, &options)
+}
+
+/// Same as [open_browser_report](fn.open_browser_report.html), but with the given options, so that
+/// each opener is really run unless [dry_run](struct.BrowserOptions.html#method.dry_run) is set
+#[cfg(not(target_arch = "wasm32"))]
+pub fn open_browser_report_with_options(
+    browser: Browser,
+    url: &str,
+    options: &BrowserOptions,
+) -> Vec<(String, Result<OpenStatus>)> {
+    // nothing gets launched during a test run, so the chain is only walked as in a dry run then
+    let mut dry_run;
+    let options = if test_sink().is_some() {
+        dry_run = options.clone();
+        dry_run.dry_run(true);
+        &dry_run
+    } else {
+        options
+    };
+    match prepare_url(url, options) {
+        Ok(url) => {
+            open_browser_report_internal(dispatch_browser(browser, &url, options), &url, options)
+        }
+        Err(e) => vec![(url.to_string(), Err(e))],
+    }
+}
+
+/// There's only the one opener on these platforms
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "haiku",
+    target_arch = "wasm32"
+)))]
+fn open_browser_report_internal(
+    browser: Browser,
+    url: &str,
+    options: &BrowserOptions,
+) -> Vec<(String, Result<OpenStatus>)> {
+    vec![(
+        format!("{} {}", browser, url),
+        open_browser_internal(browser, url, options),
+    )]
+}
+
+/// Turns `url` into the one to actually hand over to the browser, checking it's fine to do so.
+/// The user's transform goes first, then paths are turned into file: URLs if asked to, and once
+/// the URL is validated, data: URLs are written out to a temp file whose URL is used instead, on
+/// the platforms where openers choke on them (unless nothing is going to be opened anyway).
+#[cfg(not(target_arch = "wasm32"))]
+fn prepare_url<'a>(url: &'a str, options: &BrowserOptions) -> Result<Cow<'a, str>> {
+    if options.raw {
+        return Ok(Cow::Borrowed(url));
+    }
+    let url = match options.url_transform {
+        Some(Callback(ref transform)) => Cow::Owned(transform(url)),
+        None => Cow::Borrowed(url),
+    };
+    let url = if options.treat_paths_as_files
+        && url_scheme(&url).is_none()
+        && Path::new(url.as_ref()).exists()
+    {
+        Cow::Owned(path_to_url(Path::new(url.as_ref()))?)
+    } else {
+        url
+    };
+    validate_url(&url, options)?;
+    #[cfg(any(
+        windows,
+        target_os = "linux",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd",
+        target_os = "haiku"
+    ))]
+    let url = if data_url::is_data_url(&url) && !options.dry_run && test_sink().is_none() {
+        Cow::Owned(path_to_url(&data_url::materialize(&url)?)?)
+    } else {
+        url
+    };
+    #[cfg(feature = "preflight")]
+    if options.preflight {
+        preflight::check(&url)?;
+    }
+    Ok(url)
+}
+
+/// Builds a single-line command out of `program` and `args`, quoted such that it can be safely
+/// copied and pasted into the shell of the current platform (POSIX `sh` on Unix, `cmd.exe` on
+/// Windows). Handy when the URL can't be opened, and the user has to be told how to do so by hand.
+///
+/// # Examples
+/// ```
+/// use webbrowser::shell_quote_command;
+///
+/// let url = "http://example.com/?a=1&b=2";
+/// println!("couldn't open the browser, run: {}", shell_quote_command("xdg-open", &[url]));
+/// ```
+#[cfg(not(target_arch = "wasm32"))]
+pub fn shell_quote_command<S: AsRef<str>>(program: &str, args: &[S]) -> String {
+    #[cfg(windows)]
+    let quote = cmd_quote;
+    #[cfg(not(windows))]
+    let quote = posix_quote;
+    std::iter::once(program)
+        .chain(args.iter().map(AsRef::as_ref))
+        .map(quote)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Quotes `arg` for a POSIX shell, using single quotes unless it's made only of safe characters
+#[cfg(any(all(not(windows), not(target_arch = "wasm32")), test))]
+fn posix_quote(arg: &str) -> String {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "-_./:=@%+,".contains(c);
+    if !arg.is_empty() && arg.chars().all(is_safe) {
+        arg.to_string()
+    } else {
+        // a single quote can't appear inside single quotes, so close, escape it, and reopen
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+/// Quotes `arg` for `cmd.exe`, using double quotes unless it's made only of safe characters
+#[cfg(any(windows, test))]
+fn cmd_quote(arg: &str) -> String {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "-_./:=@+,\\".contains(c);
+    if !arg.is_empty() && arg.chars().all(is_safe) {
+        arg.to_string()
+    } else {
+        // variables get expanded even within quotes, so % has to be escaped outside of them
+        format!("\"{}\"", arg.replace('"', "\"\"").replace('%', "\"^%\""))
+    }
+}
+
+/// Returns the version of `browser` (e.g. `118.0`) if it's installed, or an error of kind
+/// `ErrorKind::NotFound` if not. Nothing gets opened, though on Linux and *BSD the browser's
+/// binary gets run with `--version` to find out. For `Browser::Default`, only a browser set via
+/// $BROWSER can be told, and `ErrorKind::Unsupported` is returned elsewhere.
+///
+/// # Examples
+/// ```no_run
+/// use webbrowser::{browser_version, Browser};
+///
+/// match browser_version(Browser::Firefox) {
+///     Ok(version) => println!("running against Firefox {}", version),
+///     Err(e) => println!("no Firefox: {}", e),
+/// }
+/// ```
+#[cfg(not(target_arch = "wasm32"))]
+pub fn browser_version(browser: Browser) -> Result<String> {
+    browser_version_internal(browser)
+}
+
+// Where URLs are always handed over to the system, the system is also what picks the browser and
+// launches it. So there's neither a browser process to hand back, nor a way of telling which
+// browser (let alone which version) ends up with a URL.
+
+#[cfg(any(windows, target_os = "android", target_os = "ios"))]
+#[inline]
+fn spawn_browser_internal(
+    browser: Browser,
+    url: &str,
+    options: &BrowserOptions,
+) -> Result<Option<Child>> {
+    open_browser_internal(browser, url, options)
+        .and_then(OpenStatus::into_result)
+        .map(|_| None)
+}
+
+#[cfg(any(windows, target_os = "android", target_os = "ios"))]
+fn browser_version_internal(_browser: Browser) -> Result<String> {
+    Err(Error::new(
+        ErrorKind::Unsupported,
+        "browser versions can't be told on this platform",
+    ))
+}
+
+/// Picks the version number out of what a browser says about its version, i.e. the first word
+/// which starts with a digit, like `118.0` out of `Mozilla Firefox 118.0`
+#[cfg(any(
+    target_os = "macos",
+    target_os = "linux",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "haiku",
+    test
+))]
+pub(crate) fn parse_version(output: &str) -> Option<String> {
+    output
+        .split_whitespace()
+        .find(|word| word.starts_with(|c: char| c.is_ascii_digit()))
+        .map(|word| word.trim_end_matches([',', ';']).to_string())
+}
+
+/// Returns true if something which can open URLs in `browser` is installed on this system. This
+/// only checks that the relevant commands or applications exist, without running anything.
+///
+/// # Examples
+/// ```no_run
+/// use webbrowser::{is_available, Browser};
+///
+/// if !is_available(Browser::Default) {
+///     println!("no browser found, please open the link manually");
+/// }
+/// ```
+#[cfg(not(target_arch = "wasm32"))]
+pub fn is_available(browser: Browser) -> bool {
+    is_available_internal(browser)
+}
+
+/// Opens the specified URL on the specific browser requested, but only after checking via
+/// [is_available](fn.is_available.html) that it can be opened at all. If not, this fails straight
+/// away with `ErrorKind::NotFound`, which gives a clean "no browser installed" path rather than
+/// running through every opener. Return semantics are otherwise the same as for
+/// [open](fn.open.html).
+///
+/// # Examples
+/// ```no_run
+/// use std::io::ErrorKind;
+/// use webbrowser::{ensure_available_then_open, Browser};
+///
+/// match ensure_available_then_open(Browser::Default, "http://github.com") {
+///     Err(ref e) if e.kind() == ErrorKind::NotFound => println!("no browser installed"),
+///     _ => {}
+/// }
+/// ```
+#[cfg(not(target_arch = "wasm32"))]
+pub fn ensure_available_then_open(browser: Browser, url: &str) -> Result<Output> {
+    if !is_available(browser) {
+        return Err(Error::new(
+            ErrorKind::NotFound,
+            format!("no opener found for {} browser", browser),
+        ));
+    }
+    open_browser(browser, url)
+}
+
+/// Opens the specified URL on the specific browser requested, and then waits for `settle` before
+/// returning, to give the browser some time to start up and navigate to it. This is handy in
+/// automation, where the next step expects the page to be up.
+///
+/// Note that this is just a fixed delay, and *not* a signal that the page has actually loaded (which
+/// can't be detected from the outside). If opening fails, the error is returned straight away,
+/// without waiting.
+///
+/// # Examples
+/// ```no_run
+/// use std::time::Duration;
+/// use webbrowser::{open_and_wait, Browser};
+///
+/// open_and_wait(Browser::Default, "http://localhost:8000", Duration::from_secs(2)).unwrap();
+/// // ... now talk to the page
+/// ```
+#[cfg(not(target_arch = "wasm32"))]
+pub fn open_and_wait(browser: Browser, url: &str, settle: Duration) -> Result<Output> {
+    wait_to_settle(open_browser(browser, url), settle)
+}
+
+/// Opens the specified URL on the specific browser requested, giving up with
+/// `ErrorKind::TimedOut` once `deadline` passes. This is an overall budget for the whole chain of
+/// openers tried (on Linux and *BSD): the time each one takes is deducted from what's left for
+/// the rest, and one which is still running at the deadline gets killed. This bounds how long
+/// opening can take on systems where several openers each hang for a while.
+///
+/// # Examples
+/// ```no_run
+/// use std::time::{Duration, Instant};
+/// use webbrowser::{open_browser_with_deadline, Browser};
+///
+/// let deadline = Instant::now() + Duration::from_secs(5);
+/// if open_browser_with_deadline(Browser::Default, "http://github.com", deadline).is_err() {
+///     println!("please open http://github.com yourself");
+/// }
+/// ```
+#[cfg(not(target_arch = "wasm32"))]
+pub fn open_browser_with_deadline(
+    browser: Browser,
+    url: &str,
+    deadline: Instant,
+) -> Result<Output> {
+    let options = BrowserOptions::default();
+    let url = prepare_url(url, &options)?;
+    let browser = dispatch_browser(browser, &url, &options);
+    match test_sink() {
+        // there's nothing to wait for with the sink
+        Some(sink) => open_into_test_sink(&sink, browser, &url, &options).map(|o| o.status()),
+        None => open_browser_deadline_internal(browser, &url, &options, deadline),
+    }
+    .and_then(OpenStatus::into_result)
+    .map(|_| Output {
+        status: ExitStatus::from_raw(0),
+        stdout: vec![],
+        stderr: vec![],
+    })
+}
+
+/// There's just the one opener on these platforms, which can't be cut short, so the deadline
+/// only gets checked before it
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "haiku",
+    target_arch = "wasm32"
+)))]
+fn open_browser_deadline_internal(
+    browser: Browser,
+    url: &str,
+    options: &BrowserOptions,
+    deadline: Instant,
+) -> Result<OpenStatus> {
+    if Instant::now() >= deadline {
+        return Err(Error::new(
+            ErrorKind::TimedOut,
+            "gave up, as the deadline for opening the url passed",
+        ));
+    }
+    open_browser_internal(browser, url, options)
+}
+
+/// Opens each of `urls` in turn in the specific browser requested, waiting for the
+/// [inter_open_delay](struct.BrowserOptions.html#method.inter_open_delay) in between. The
+/// [OpenStatus](struct.OpenStatus.html) of each launch is returned, in the same order as `urls`.
+/// Opening stops at the first URL which can't be opened at all, whose error is returned. To also
+/// stop at a launch which reports failure, see [open_sequence](fn.open_sequence.html).
+///
+/// # Examples
+/// ```no_run
+/// use std::time::Duration;
+/// use webbrowser::{open_multiple, Browser, BrowserOptions};
+///
+/// let urls = ["http://github.com", "http://docs.rs", "http://crates.io"];
+/// let mut options = BrowserOptions::new();
+/// options.inter_open_delay(Duration::from_millis(250));
+/// open_multiple(Browser::Default, &urls, &options).unwrap();
+/// ```
+#[cfg(not(target_arch = "wasm32"))]
+pub fn open_multiple<S: AsRef<str>>(
+    browser: Browser,
+    urls: &[S],
+    options: &BrowserOptions,
+) -> Result<Vec<OpenStatus>> {
+    open_paced(urls, options.inter_open_delay, |url| {
+        open_browser_status(browser, url, options)
+    })
+}
+
+/// Opens each of `urls` in turn in the default browser, returning the error of the first one which
+/// fails without attempting the rest. Unlike with [open_multiple](fn.open_multiple.html), a
+/// launch reporting failure (like an opener exiting with an error) counts as one too, which suits
+/// URLs depending on the ones before them having been opened.
+///
+/// # Examples
+/// ```no_run
+/// let urls = ["http://localhost:8080/login", "http://localhost:8080/dashboard"];
+/// webbrowser::open_sequence(&urls).unwrap();
+/// ```
+#[cfg(not(target_arch = "wasm32"))]
+pub fn open_sequence<S: AsRef<str>>(urls: &[S]) -> Result<()> {
+    let options = BrowserOptions::default();
+    open_until_failure(urls, |url| {
+        open_browser_status(Browser::Default, url, &options)
+    })
+}
+
+/// Calls `open` with each of `urls` until one of them fails
+#[cfg(not(target_arch = "wasm32"))]
+fn open_until_failure<S: AsRef<str>>(
+    urls: &[S],
+    mut open: impl FnMut(&str) -> Result<OpenStatus>,
+) -> Result<()> {
+    urls.iter().try_for_each(|url| {
+        open(url.as_ref())
+            .and_then(OpenStatus::into_result)
+            .map(drop)
+    })
+}
+
+/// Calls `open` with each of `urls`, sleeping for `delay` in between
+#[cfg(not(target_arch = "wasm32"))]
+fn open_paced<S: AsRef<str>>(
+    urls: &[S],
+    delay: Duration,
+    mut open: impl FnMut(&str) -> Result<OpenStatus>,
+) -> Result<Vec<OpenStatus>> {
+    let mut statuses = Vec::with_capacity(urls.len());
+    for (i, url) in urls.iter().enumerate() {
+        if i > 0 {
+            thread::sleep(delay);
+        }
+        statuses.push(open(url.as_ref())?);
+    }
+    Ok(statuses)
+}
+
+/// Works out how URLs would be opened in the specific browser requested, without opening any. The
+/// returned [ResolvedOpener](struct.ResolvedOpener.html) then opens URLs the same way, skipping
+/// the search for `$BROWSER` entries and openers on `$PATH` which otherwise precedes every open.
+/// This pays off when opening lots of URLs, e.g. from a long-running server.
+///
+/// As nothing is run while resolving, the first opener which is installed is the one taken, so
+/// (unlike with [open_browser](fn.open_browser.html)) there's no moving on to the next one if it
+/// turns out not to work.
+///
+/// # Examples
+/// ```no_run
+/// use webbrowser::{resolve_opener, Browser};
+///
+/// let opener = resolve_opener(Browser::Default).unwrap();
+/// for page in 1..=10 {
+///     opener.open(&format!("http://localhost:8000/page/{}", page)).unwrap();
+/// }
+/// ```
+#[cfg(not(target_arch = "wasm32"))]
+pub fn resolve_opener(browser: Browser) -> Result<ResolvedOpener> {
+    resolve_opener_with_options(browser, &BrowserOptions::default())
+}
+
+/// Same as [resolve_opener](fn.resolve_opener.html), but with the given options, which apply to
+/// every URL opened with the returned opener. Those deciding on the command (like
+/// [incognito](struct.BrowserOptions.html#method.incognito)) are baked into it.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn resolve_opener_with_options(
+    browser: Browser,
+    options: &BrowserOptions,
+) -> Result<ResolvedOpener> {
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd",
+        target_os = "haiku"
+    ))]
+    {
+        let mut command = None;
+        let outcome = open_with_fallback(browser, options, |browser| {
+            resolve_opener_internal(browser, options).map(|resolved| {
+                command = Some(resolved);
+                OpenStatus::launched()
+            })
+        })?;
+        Ok(ResolvedOpener {
+            browser: outcome.browser(),
+            options: options.clone(),
+            command: command.expect("resolved without a command"),
+        })
+    }
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd",
+        target_os = "haiku"
+    )))]
+    {
+        // there's no searching to be saved here, so it's just about the browser being there
+        let outcome = open_with_fallback(browser, options, |browser| {
+            if is_available_internal(browser) {
+                Ok(OpenStatus::launched())
+            } else {
+                Err(Error::new(
+                    ErrorKind::NotFound,
+                    format!("{} not found", browser),
+                ))
+            }
+        })?;
+        Ok(ResolvedOpener {
+            browser: outcome.browser(),
+            options: options.clone(),
+        })
+    }
+}
+
+/// A browser, along with the way URLs are opened in it, as worked out by
+/// [resolve_opener](fn.resolve_opener.html)
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone)]
+pub struct ResolvedOpener {
+    browser: Browser,
+    options: BrowserOptions,
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd",
+        target_os = "haiku"
+    ))]
+    command: ResolvedCommand,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl ResolvedOpener {
+    /// Returns the browser URLs are opened in, which is the default browser if the requested one
+    /// wasn't found and [fallback_to_default](struct.BrowserOptions.html#method.fallback_to_default)
+    /// is set
+    pub fn browser(&self) -> Browser {
+        self.browser
+    }
+
+    /// Opens `url` the way that was resolved, returning the [OpenStatus](struct.OpenStatus.html)
+    /// of the launch. URLs with schemes other than the ones browsers deal with (like `mailto:`)
+    /// still go to whatever handler the system has for them, as with
+    /// [open_browser_status](fn.open_browser_status.html).
+    pub fn open(&self, url: &str) -> Result<OpenStatus> {
+        let options = &self.options;
+        let url = prepare_url(url, options)?;
+        if let Some(sink) = test_sink() {
+            return open_into_test_sink(&sink, self.browser, &url, options)
+                .map(|outcome| outcome.status());
+        }
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "freebsd",
+            target_os = "netbsd",
+            target_os = "openbsd",
+            target_os = "haiku"
+        ))]
+        {
+            if options.raw || !has_custom_scheme(&url) {
+                return open_resolved_internal(&self.command, &url, options);
+            }
+        }
+        open_browser_internal(dispatch_browser(self.browser, &url, options), &url, options)
+    }
+}
+
+/// Sleeps for `settle` if `result` is a success
+#[cfg(not(target_arch = "wasm32"))]
+fn wait_to_settle<T>(result: Result<T>, settle: Duration) -> Result<T> {
+    if result.is_ok() {
+        thread::sleep(settle);
+    }
+    result
+}
+
+/// Opens the specified URL on the specific browser (if available) requested, and returns a
+/// [BrowserSession](struct.BrowserSession.html) through which the browser can later be closed.
+///
+/// Only browsers which the crate launches directly can be tracked like this (currently the named
+/// browsers on Linux and *BSD). If the URL was instead handed off to the system (via `xdg-open`,
+/// `open`, `ShellExecuteW` etc.), the real browser process is out of reach, and an unmanaged
+/// session is returned.
+///
+/// # Examples
+/// ```no_run
+/// use webbrowser::{open_browser_managed, Browser};
+///
+/// let mut session = open_browser_managed(Browser::Firefox, "http://localhost:8000").unwrap();
+/// // ... later, when the user hits stop
+/// if session.is_managed() {
+///     session.close().unwrap();
+/// }
+/// ```
+#[cfg(not(target_arch = "wasm32"))]
+pub fn open_browser_managed(browser: Browser, url: &str) -> Result<BrowserSession> {
+    let options = BrowserOptions::default();
+    let url = prepare_url(url, &options)?;
+    spawn_session(dispatch_browser(browser, &url, &options), &url, &options)
+}
+
+/// Starts up the specific browser requested on a blank page, and returns a
+/// [BrowserSession](struct.BrowserSession.html) for it, the same way as
+/// [open_browser_managed](fn.open_browser_managed.html) does. This is handy to get the browser
+/// going ahead of time (e.g. while a server is still starting up), as URLs opened later on are
+/// then handed over to the running instance, which is quicker than a cold start.
+///
+/// This doesn't work with `Browser::Terminal`, as text browsers take over the terminal, and so
+/// can't be started in the background.
+///
+/// # Examples
+/// ```no_run
+/// use webbrowser::{launch_browser, open_browser, Browser};
+///
+/// let session = launch_browser(Browser::Firefox).unwrap();
+/// // ... start up the server
+/// open_browser(Browser::Firefox, "http://localhost:8000").unwrap();
+/// ```
+#[cfg(not(target_arch = "wasm32"))]
+pub fn launch_browser(browser: Browser) -> Result<BrowserSession> {
+    if browser == Browser::Terminal {
+        return Err(Error::new(
+            ErrorKind::Unsupported,
+            "text browsers can't be launched ahead of time",
+        ));
+    }
+    spawn_session(browser, BLANK_PAGE, &BrowserOptions::default())
+}
+
+/// The page browsers are launched on by [launch_browser](fn.launch_browser.html), which they all
+/// understand
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) const BLANK_PAGE: &str = "about:blank";
+
+/// Launches `browser` on `url`, keeping hold of the process if possible
+#[cfg(not(target_arch = "wasm32"))]
+fn spawn_session(browser: Browser, url: &str, options: &BrowserOptions) -> Result<BrowserSession> {
+    if let Some(sink) = test_sink() {
+        return open_into_test_sink(&sink, browser, url, options)
+            .map(|_| BrowserSession { child: None });
+    }
+    spawn_browser_internal(browser, url, options).map(|child| BrowserSession { child })
+}
+
+/// Handle to a browser opened via [open_browser_managed](fn.open_browser_managed.html)
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug)]
+pub struct BrowserSession {
+    child: Option<Child>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl BrowserSession {
+    /// Returns true if the browser process is tracked by this session, in which case it can be
+    /// closed or waited upon
+    pub fn is_managed(&self) -> bool {
+        self.child.is_some()
+    }
+
+    /// Returns the process id of the browser, if managed
+    pub fn id(&self) -> Option<u32> {
+        self.child.as_ref().map(Child::id)
+    }
+
+    /// Asks the browser to close (via SIGTERM on unix, and TerminateProcess on Windows), and
+    /// waits for it to exit. Fails with `ErrorKind::Unsupported` for an unmanaged session.
+    pub fn close(&mut self) -> Result<()> {
+        let child = self.child_mut()?;
+        if child.try_wait()?.is_some() {
+            // already gone
+            return Ok(());
+        }
+        #[cfg(unix)]
+        {
+            if unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGTERM) } != 0 {
+                return Err(Error::last_os_error());
+            }
+        }
+        #[cfg(not(unix))]
+        child.kill()?;
+        child.wait().map(|_| ())
+    }
+
+    /// Waits for the browser to exit on its own. Fails with `ErrorKind::Unsupported` for an
+    /// unmanaged session.
+    pub fn wait(&mut self) -> Result<OpenStatus> {
+        self.child_mut()?.wait().map(OpenStatus::from)
+    }
+
+    fn child_mut(&mut self) -> Result<&mut Child> {
+        self.child.as_mut().ok_or_else(|| {
+            Error::new(
+                ErrorKind::Unsupported,
+                "browser was launched via the system, and can't be tracked",
+            )
+        })
+    }
+}
+
+/// Platform-neutral status of a browser launch, so that [success](#method.success) and
+/// [code](#method.code) mean the same thing everywhere.
+#[derive(Debug, Eq, PartialEq, Copy, Clone, Hash)]
+pub struct OpenStatus {
+    success: bool,
+    code: Option<i32>,
+}
+
+impl OpenStatus {
+    /// Status for a launch which was handed off successfully, but whose outcome can't be known,
+    /// e.g. when a browser is spawned without waiting for it
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn launched() -> Self {
+        OpenStatus {
+            success: true,
+            code: None,
+        }
+    }
+
+    /// Turns an unsuccessful status into the corresponding Err(..)
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn into_result(self) -> Result<OpenStatus> {
+        if self.success {
+            Ok(self)
+        } else if let Some(code) = self.code {
+            Err(Error::other(format!("return code {}", code)))
+        } else {
+            Err(Error::other("interrupted by signal"))
+        }
+    }
+
+    /// Returns true if the browser (or the opener handing off to it) launched successfully
+    pub fn success(&self) -> bool {
+        self.success
+    }
+
+    /// Returns the exit code of the opener, if it ran to completion. This is `None` if the opener
+    /// was terminated by a signal, or if the launch was successfully handed off without an exit
+    /// code to report.
+    pub fn code(&self) -> Option<i32> {
+        self.code
+    }
+}
+
+/// What came of opening a URL via [open_browser_outcome](fn.open_browser_outcome.html)
+#[derive(Debug, Eq, PartialEq, Copy, Clone, Hash)]
+pub struct OpenOutcome {
+    status: OpenStatus,
+    browser: Browser,
+    fell_back: bool,
+}
+
+impl OpenOutcome {
+    /// Returns the status of the launch
+    pub fn status(&self) -> OpenStatus {
+        self.status
+    }
+
+    /// Returns the browser the URL was opened in
+    pub fn browser(&self) -> Browser {
+        self.browser
+    }
+
+    /// Returns true if the requested browser couldn't be found, and the default browser was
+    /// used instead
+    pub fn fell_back(&self) -> bool {
+        self.fell_back
+    }
+}
+
+impl From<ExitStatus> for OpenStatus {
+    fn from(status: ExitStatus) -> Self {
+        OpenStatus {
+            success: status.success(),
+            code: status.code(),
+        }
+    }
+}
+
+#[cfg(not(any(
+    target_os = "android",
+    target_os = "ios",
+    target_os = "windows",
+    target_os = "macos",
+    target_os = "linux",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "haiku",
+    target_arch = "wasm32"
+)))]
+compile_error!(
+    "Only Windows, Mac OS, iOS, Linux, *BSD, Haiku, Android and Wasm32 are currently supported"
+);
+
+#[cfg(all(target_os = "ios", not(feature = "ios")))]
+compile_error!("iOS is only supported with the `ios` feature enabled");
+
+#[test]
+#[ignore]
+fn test_open_firefox() {
+    assert!(open_browser(Browser::Firefox, "http://github.com").is_ok());
+}
+
+#[test]
+#[ignore]
+fn test_open_chrome() {
+    assert!(open_browser(Browser::Chrome, "http://github.com").is_ok());
+}
+
+#[test]
+#[cfg(target_arch = "wasm32")]
+fn test_open_default_wasm() {
+    assert!(open("http://github.com").is_ok());
+}
+
+#[test]
+#[ignore]
+fn test_open_safari() {
+    assert!(open_browser(Browser::Safari, "http://github.com").is_ok());
+}
+
+#[test]
+#[ignore]
+fn test_open_webpositive() {
+    assert!(open_browser(Browser::WebPositive, "http://github.com").is_ok());
+}
+
+#[test]
+fn test_url_len_at_limit() {
+    let mut options = BrowserOptions::new();
+    options.max_url_len(20);
+    let url = "http://example.com/a";
+    assert_eq!(url.len(), 20);
+    assert!(validate_url(url, &options).is_ok());
+}
+
+#[test]
+fn test_url_len_over_limit() {
+    let mut options = BrowserOptions::new();
+    options.max_url_len(19);
+    let err = validate_url("http://example.com/a", &options).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+}
+
+#[test]
+fn test_url_len_default_limit() {
+    let options = BrowserOptions::default();
+    let url = format!("http://example.com/{}", "a".repeat(DEFAULT_MAX_URL_LEN));
+    assert!(validate_url(&url[..DEFAULT_MAX_URL_LEN], &options).is_ok());
+    assert!(validate_url(&url, &options).is_err());
+    assert!(validate_url(&url, BrowserOptions::new().max_url_len(usize::MAX)).is_ok());
+}
+
+#[test]
+fn test_open_status_from_exit_status() {
+    let status = OpenStatus::from(ExitStatus::from_raw(0));
+    assert!(status.success());
+    assert_eq!(status.code(), Some(0));
+}
+
+#[test]
+#[cfg(unix)]
+fn test_open_status_from_failed_exit_status() {
+    // the raw value on unix is a wait status, with the exit code in the high byte
+    let status = OpenStatus::from(ExitStatus::from_raw(2 << 8));
+    assert!(!status.success());
+    assert_eq!(status.code(), Some(2));
+
+    // killed by SIGTERM
+    let status = OpenStatus::from(ExitStatus::from_raw(15));
+    assert!(!status.success());
+    assert_eq!(status.code(), None);
+}
+
+#[test]
+#[cfg(windows)]
+fn test_open_status_from_failed_exit_status() {
+    let status = OpenStatus::from(ExitStatus::from_raw(2));
+    assert!(!status.success());
+    assert_eq!(status.code(), Some(2));
+}
+
+#[test]
+fn test_open_status_launched() {
+    let status = OpenStatus::launched();
+    assert!(status.success());
+    assert_eq!(status.code(), None);
+}
+
+#[test]
+fn test_parse_browser_name() {
+    assert_eq!(parse_browser_name("firefox").unwrap(), Browser::Firefox);
+    assert_eq!(parse_browser_name("Chrome").unwrap(), Browser::Chrome);
+    assert_eq!(parse_browser_name("default").unwrap(), Browser::Default);
+}
+
+#[test]
+fn test_browser_names_parse() {
+    for name in BROWSER_NAMES {
+        assert!(name.parse::<Browser>().is_ok(), "{} should parse", name);
+    }
+}
+
+#[test]
+fn test_open_in_unknown_browser() {
+    let err = open_in("netscape", "http://github.com").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    let msg = err.to_string();
+    assert!(msg.contains("netscape"));
+    assert!(msg.contains("firefox, ie, chrome"));
+}
+
+#[test]
+fn test_url_disallowed_chars() {
+    let options = BrowserOptions::default();
+    for url in &[
+        "http://example.com/a\nb",
+        "http://example.com/\u{7f}",
+        "http://example.com/a b",
+        "http://example.com/<script>",
+    ] {
+        let err = validate_url(url, &options).unwrap_err();
+        assert_eq!(
+            err.kind(),
+            ErrorKind::InvalidInput,
+            "{:?} should be rejected",
+            url
+        );
+    }
+}
+
+#[test]
+fn test_url_allowed_chars() {
+    let options = BrowserOptions::default();
+    for url in &[
+        "http://例え.テスト/パス",
+        "http://bücher.example/ｎｏｎａｓｃｉｉ",
+        "http://example.com/%20a?q=1&r=[2]#frag",
+    ] {
+        assert!(
+            validate_url(url, &options).is_ok(),
+            "{:?} should be allowed",
+            url
+        );
+    }
+}
+
+#[test]
+fn test_url_lenient_validation() {
+    let mut options = BrowserOptions::new();
+    options.lenient_url_validation(true);
+    assert!(validate_url("http://example.com/a b", &options).is_ok());
+}
+
+#[test]
+#[cfg(unix)]
+fn test_path_to_url() {
+    assert_eq!(
+        path_to_url(Path::new("/home/me/my report#1.html")).unwrap(),
+        "file:///home/me/my%20report%231.html"
+    );
+    assert_eq!(
+        path_to_url(Path::new("/tmp/ｎｏｎａｓｃｉｉ")).unwrap(),
+        "file:///tmp/%EF%BD%8E%EF%BD%8F%EF%BD%8E%EF%BD%81%EF%BD%93%EF%BD%83%EF%BD%89%EF%BD%89"
+    );
+    let relative = path_to_url(Path::new("report.html")).unwrap();
+    assert!(relative.starts_with("file:///"));
+    assert!(relative.ends_with("/report.html"));
+}
+
+#[test]
+#[cfg(not(target_arch = "wasm32"))]
+fn test_wait_to_settle() {
+    let settle = Duration::from_millis(100);
+    let start = std::time::Instant::now();
+    assert!(wait_to_settle(Ok(()), settle).is_ok());
+    assert!(start.elapsed() >= settle);
+
+    let start = std::time::Instant::now();
+    let failed: Result<()> = Err(Error::new(ErrorKind::NotFound, "not found"));
+    assert!(wait_to_settle(failed, Duration::from_secs(10)).is_err());
+    assert!(start.elapsed() < Duration::from_secs(10));
+}
+
+#[test]
+fn test_open_paced() {
+    let delay = Duration::from_millis(50);
+    let mut opened = Vec::new();
+    let start = std::time::Instant::now();
+    let statuses = open_paced(&["a:1", "a:2", "a:3"], delay, |url| {
+        opened.push((url.to_string(), start.elapsed()));
+        Ok(OpenStatus::launched())
+    })
+    .unwrap();
+    assert_eq!(statuses.len(), 3);
+    assert!(start.elapsed() >= delay * 2);
+    assert_eq!(opened[2].0, "a:3");
+    assert!(opened[1].1 >= delay && opened[2].1 >= delay * 2);
+
+    // no waiting after the last one, nor after a failure
+    let start = std::time::Instant::now();
+    let mut calls = 0;
+    let err = open_paced(&["a:1", "a:2"], Duration::from_secs(10), |_| {
+        calls += 1;
+        Err(Error::new(ErrorKind::NotFound, "not found"))
+    })
+    .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+    assert_eq!(calls, 1);
+    assert!(start.elapsed() < Duration::from_secs(10));
+}
+
+#[test]
+fn test_open_until_failure() {
+    let mut opened = Vec::new();
+    let err = open_until_failure(&["a:1", "a:2", "a:3"], |url| {
+        opened.push(url.to_string());
+        Ok(OpenStatus {
+            success: url != "a:2",
+            code: Some(if url == "a:2" { 3 } else { 0 }),
+        })
+    })
+    .unwrap_err();
+    assert_eq!(err.to_string(), "return code 3");
+    assert_eq!(opened, vec!["a:1", "a:2"]);
+
+    let mut calls = 0;
+    open_until_failure(&["a:1", "a:2"], |_| {
+        calls += 1;
+        Ok(OpenStatus::launched())
+    })
+    .unwrap();
+    assert_eq!(calls, 2);
+}
+
+#[test]
+#[cfg(any(
+    windows,
+    target_os = "linux",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "haiku"
+))]
+fn test_data_url_materialized_once_valid() {
+    let url = "data:text/html,%3Ch1%3Ehi%3C%2Fh1%3E";
+    let opened = prepare_url(url, &BrowserOptions::new()).unwrap();
+    assert!(opened.starts_with("file:"), "{}", opened);
+    let file_name = opened.rsplit('/').next().unwrap();
+    assert!(file_name.starts_with("webbrowser-data-"), "{}", opened);
+    fs::remove_file(std::env::temp_dir().join(file_name)).unwrap();
+
+    // nothing is written out if nothing is going to be opened
+    let mut options = BrowserOptions::new();
+    options.dry_run(true);
+    assert_eq!(prepare_url(url, &options).unwrap(), url);
+
+    // the length cap applies to the data: URL itself
+    let mut options = BrowserOptions::new();
+    options.max_url_len(16);
+    let err = prepare_url(url, &options).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+}
+
+#[test]
+fn test_treat_paths_as_files() {
+    let mut options = BrowserOptions::new();
+    options.treat_paths_as_files(true);
+    // tests are run from the crate's root
+    let url = prepare_url("Cargo.toml", &options).unwrap();
+    assert!(
+        url.starts_with("file://") && url.ends_with("/Cargo.toml"),
+        "{}",
+        url
+    );
+    assert_eq!(prepare_url("example.com", &options).unwrap(), "example.com");
+    assert_eq!(
+        prepare_url("http://Cargo.toml", &options).unwrap(),
+        "http://Cargo.toml"
+    );
+    assert_eq!(
+        prepare_url("Cargo.toml", &BrowserOptions::new()).unwrap(),
+        "Cargo.toml"
+    );
+}
+
+#[test]
+fn test_raw_url() {
+    use std::sync::Mutex;
+
+    let url = "steam://run/440/ -bad \"quotes\" %zz\u{7}<>";
+    let tried = Arc::new(Mutex::new(Vec::new()));
+    let sink = tried.clone();
+    let mut options = BrowserOptions::new();
+    options
+        .raw(true)
+        .max_url_len(8)
+        .dry_run(true)
+        .dry_run_sink(move |_, args| sink.lock().unwrap().extend(args.to_vec()));
+    assert_eq!(prepare_url(url, &options).unwrap(), url);
+    assert_eq!(
+        dispatch_browser(Browser::Firefox, url, &options),
+        Browser::Firefox
+    );
+    let _ = open_browser_status(Browser::Default, url, &options);
+    assert!(tried.lock().unwrap().iter().any(|arg| arg == url));
+
+    options.raw(false);
+    assert!(prepare_url(url, &options).is_err());
+}
+
+#[test]
+fn test_posix_quote() {
+    let url = "http://example.com/a b?x=1&y=$HOME";
+    assert_eq!(posix_quote(url), "'http://example.com/a b?x=1&y=$HOME'");
+    assert_eq!(posix_quote("it's"), "'it'\\''s'");
+    assert_eq!(
+        posix_quote("http://example.com/%20"),
+        "http://example.com/%20"
+    );
+    assert_eq!(posix_quote(""), "''");
+}
+
+#[test]
+fn test_cmd_quote() {
+    assert_eq!(
+        cmd_quote("http://example.com/a b?x=1&y=$HOME"),
+        "\"http://example.com/a b?x=1&y=$HOME\""
+    );
+    assert_eq!(
+        cmd_quote("http://example.com/%PATH%"),
+        "\"http://example.com/\"^%\"PATH\"^%\"\""
+    );
+    assert_eq!(cmd_quote(""), "\"\"");
+}
+
+#[test]
+#[cfg(unix)]
+fn test_shell_quote_command() {
+    assert_eq!(
+        shell_quote_command("xdg-open", &["http://example.com/?a=1&b=2"]),
+        "xdg-open 'http://example.com/?a=1&b=2'"
+    );
+}
+
+#[test]
+fn test_browser_capabilities() {
+    let firefox = Browser::Firefox.capabilities();
+    assert!(firefox.incognito && firefox.profile);
+    assert!(!firefox.app_mode);
+    assert_eq!(
+        Browser::Chrome.capabilities(),
+        BrowserCapabilities {
+            incognito: true,
+            new_window: true,
+            app_mode: true,
+            profile: true,
+            kiosk: true,
+        }
+    );
+    assert_eq!(
+        Browser::Terminal.capabilities(),
+        BrowserCapabilities::default()
+    );
+}
+
+#[test]
+#[cfg(not(target_arch = "wasm32"))]
+fn test_url_transform() {
+    use std::sync::Mutex;
+
+    let tried = Arc::new(Mutex::new(Vec::new()));
+    let sink = tried.clone();
+    let mut options = BrowserOptions::new();
+    options
+        .dry_run(true)
+        .dry_run_sink(move |_, args| sink.lock().unwrap().extend(args.to_vec()))
+        .url_transform(|url| url.replace("http://internal/", "https://gateway.example.com/"));
+    let _ = open_browser_status(Browser::Default, "http://internal/wiki", &options);
+    let tried = tried.lock().unwrap();
+    assert!(tried
+        .iter()
+        .any(|arg| arg.contains("https://gateway.example.com/wiki")));
+    assert!(!tried.iter().any(|arg| arg.contains("http://internal/")));
+
+    // the transformed url is what gets validated
+    options.url_transform(|url| format!("{}/<bad>", url));
+    let err = open_browser_status(Browser::Default, "http://example.com", &options).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+}
+
+#[test]
+#[cfg(not(target_arch = "wasm32"))]
+fn test_custom_scheme_dispatch() {
+    assert_eq!(
+        dispatch_browser(Browser::Firefox, "steam://run/440", &BrowserOptions::new()),
+        Browser::Default
+    );
+    assert_eq!(
+        dispatch_browser(
+            Browser::Chrome,
+            "zoommtg://zoom.us/join?confno=1",
+            &BrowserOptions::new()
+        ),
+        Browser::Default
+    );
+    assert_eq!(
+        dispatch_browser(
+            Browser::Firefox,
+            "HTTPS://example.com",
+            &BrowserOptions::new()
+        ),
+        Browser::Firefox
+    );
+    assert_eq!(
+        dispatch_browser(Browser::Firefox, "about:blank", &BrowserOptions::new()),
+        Browser::Firefox
+    );
+    assert_eq!(url_scheme("vscode://file/x"), Some("vscode".to_string()));
+    assert_eq!(url_scheme(r"C:\Users\me"), None);
+    assert_eq!(url_scheme("example.com/a:b"), None);
+}
+
+#[test]
+#[cfg(not(target_arch = "wasm32"))]
+fn test_parse_version() {
+    let parse = |output| parse_version(output).unwrap();
+    assert_eq!(parse("Mozilla Firefox 118.0\n"), "118.0");
+    assert_eq!(parse("Mozilla Firefox 115.3.1esr"), "115.3.1esr");
+    assert_eq!(parse("Google Chrome 118.0.5993.70 \n"), "118.0.5993.70");
+    assert_eq!(
+        parse("Chromium 118.0.5993.70 built on Debian 12.2, running on Debian 12.2"),
+        "118.0.5993.70"
+    );
+    assert_eq!(parse("Opera 102.0.4880.40\n"), "102.0.4880.40");
+    assert_eq!(parse("Brave Browser 1.59.117"), "1.59.117");
+    assert_eq!(parse("17.0\n"), "17.0");
+    assert_eq!(parse_version("unknown option --version"), None);
 }
@@ -1,29 +1,245 @@
-use crate::{Browser, Error, ErrorKind, Result};
+use crate::flags::browser_args;
+use crate::{Browser, BrowserOptions, Error, ErrorKind, OpenStatus, Result};
 pub use std::os::unix::process::ExitStatusExt;
-use std::process::{Command, ExitStatus};
+use std::path::PathBuf;
+use std::process::{Child, Command};
 
 /// Deal with opening of browsers on Mac OS X, using `open` command
+///
+/// If a new or background tab is requested via [BrowserOptions], and the browser is scriptable
+/// (Safari or Chrome), the url is opened via AppleScript instead, falling back to `open` if that
+/// doesn't work out.
 #[inline]
-pub fn open_browser_internal(browser: Browser, url: &str) -> Result<ExitStatus> {
+pub fn open_browser_internal(
+    browser: Browser,
+    url: &str,
+    options: &BrowserOptions,
+) -> Result<OpenStatus> {
+    let app: Option<&str> = match browser {
+        Browser::Default => None,
+        Browser::Firefox => Some("Firefox"),
+        Browser::Chrome => Some("Google Chrome"),
+        Browser::Opera => Some("Opera"),
+        Browser::Safari => Some("Safari"),
+        Browser::WebPositive => Some("WebPositive"),
+        _ => return Err(crate::unsupported_browser(browser)),
+    };
+    let args = match app {
+        // `open -n` starts up a new instance regardless, so it's never worth checking
+        Some(name) => browser_args(name, options, || false, |key| std::env::var(key).ok())?,
+        None if options.incognito => {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "incognito mode is not supported for the default browser",
+            ))
+        }
+        None => Vec::new(),
+    };
+    if (options.new_tab || options.background) && !options.incognito {
+        if let Some(script) = app.and_then(|app| applescript(app, url, options.background)) {
+            if let Ok(status) = options.run(Command::new("osascript").arg("-e").arg(&script)) {
+                if status.success() {
+                    return Ok(status);
+                }
+            }
+        }
+    }
     let mut cmd = Command::new("open");
-    match browser {
-        Browser::Default => cmd.arg(url).status(),
-        _ => {
-            let app: Option<&str> = match browser {
-                Browser::Firefox => Some("Firefox"),
-                Browser::Chrome => Some("Google Chrome"),
-                Browser::Opera => Some("Opera"),
-                Browser::Safari => Some("Safari"),
-                Browser::WebPositive => Some("WebPositive"),
-                _ => None,
+    if options.background {
+        cmd.arg("-g");
+    }
+    match app {
+        Some(name) if !args.is_empty() => {
+            // a new instance is needed for the args to reach the browser, as `open` drops them if
+            // it's already running. Most browsers hand the url over to the running instance anyway.
+            cmd.arg("-n").arg("-a").arg(name).arg("--args").args(args);
+        }
+        Some(name) => {
+            cmd.arg("-a").arg(name);
+        }
+        None => {}
+    }
+    cmd.arg(url);
+    options.run(&mut cmd)
+}
+
+/// Browsers are always launched via `open` here, so there's no process to hand back
+#[inline]
+pub fn spawn_browser_internal(
+    browser: Browser,
+    url: &str,
+    options: &BrowserOptions,
+) -> Result<Option<Child>> {
+    open_browser_internal(browser, url, options)
+        .and_then(OpenStatus::into_result)
+        .map(|_| None)
+}
+
+/// Returns true if there's something installed which can open `browser`. `open` is always around
+/// for the default browser, while named ones are looked up among the installed applications.
+pub fn is_available_internal(browser: Browser) -> bool {
+    browser == Browser::Default || find_app(browser).is_some()
+}
+
+/// Returns the version of `browser`, as recorded in its application bundle
+pub fn browser_version_internal(browser: Browser) -> Result<String> {
+    if browser == Browser::Default {
+        return Err(Error::new(
+            ErrorKind::Unsupported,
+            "the version of the default browser can't be told",
+        ));
+    }
+    let bundle = find_app(browser)
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("{} is not installed", browser)))?;
+    // reading it from Info.plist means not having to launch the browser
+    let output = Command::new("defaults")
+        .arg("read")
+        .arg(bundle.join("Contents/Info"))
+        .arg("CFBundleShortVersionString")
+        .output()?;
+    crate::parse_version(&String::from_utf8_lossy(&output.stdout))
+        .ok_or_else(|| Error::other(format!("couldn't make out the version of {}", browser)))
+}
+
+/// Returns the path of the application bundle of `browser`, if it's installed
+fn find_app(browser: Browser) -> Option<PathBuf> {
+    let app = match browser {
+        Browser::Firefox => "Firefox",
+        Browser::Chrome => "Google Chrome",
+        Browser::Opera => "Opera",
+        Browser::Safari => "Safari",
+        Browser::WebPositive => "WebPositive",
+        _ => return None,
+    };
+    let bundle = format!("{}.app", app);
+    let mut dirs = vec![
+        PathBuf::from("/Applications"),
+        PathBuf::from("/System/Applications"),
+        PathBuf::from("/System/Cryptexes/App/System/Applications"),
+    ];
+    if let Some(home) = std::env::var_os("HOME") {
+        dirs.push(PathBuf::from(home).join("Applications"));
+    }
+    dirs.into_iter()
+        .map(|dir| dir.join(&bundle))
+        .find(|path| path.exists())
+}
+
+/// Generates the AppleScript to open `url` in a new tab of the front window of `app`, or `None`
+/// if `app` isn't scriptable this way. If `background` is set, the tab which was active stays so,
+/// and the browser isn't activated.
+fn applescript(app: &str, url: &str, background: bool) -> Option<String> {
+    let url = applescript_quote(url);
+    let activate = if background { "" } else { "\n    activate" };
+    match app {
+        "Safari" => {
+            let open_tab = if background {
+                format!(
+                    "make new tab at end of tabs with properties {{URL:{}}}",
+                    url
+                )
+            } else {
+                format!(
+                    "set current tab to (make new tab at end of tabs with properties {{URL:{}}})",
+                    url
+                )
             };
-            match app {
-                Some(name) => cmd.arg("-a").arg(name).arg(url).status(),
-                None => Err(Error::new(
-                    ErrorKind::NotFound,
-                    format!("Unsupported browser {:?}", browser),
-                )),
-            }
+            Some(format!(
+                "tell application \"Safari\"
+    if (count of windows) is 0 then
+        make new document with properties {{URL:{url}}}
+    else
+        tell front window to {open_tab}
+    end if{activate}
+end tell",
+                url = url,
+                open_tab = open_tab,
+                activate = activate
+            ))
+        }
+        "Google Chrome" => {
+            let open_tab = if background {
+                format!(
+                    "set previous to active tab index
+        make new tab with properties {{URL:{}}}
+        set active tab index to previous",
+                    url
+                )
+            } else {
+                format!("make new tab with properties {{URL:{}}}", url)
+            };
+            Some(format!(
+                "tell application \"Google Chrome\"
+    if (count of windows) is 0 then
+        make new window
+        set URL of active tab of front window to {url}
+    else
+        tell front window
+        {open_tab}
+        end tell
+    end if{activate}
+end tell",
+                url = url,
+                open_tab = open_tab,
+                activate = activate
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// Quotes `s` as an AppleScript string literal, escaping anything which could otherwise terminate
+/// the literal and inject script
+fn applescript_quote(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            _ => quoted.push(c),
         }
     }
+    quoted.push('"');
+    quoted
+}
+
+#[test]
+fn test_applescript_safari() {
+    let script = applescript("Safari", "http://example.com/?q=1", false).unwrap();
+    assert_eq!(
+        script,
+        r#"tell application "Safari"
+    if (count of windows) is 0 then
+        make new document with properties {URL:"http://example.com/?q=1"}
+    else
+        tell front window to set current tab to (make new tab at end of tabs with properties {URL:"http://example.com/?q=1"})
+    end if
+    activate
+end tell"#
+    );
+}
+
+#[test]
+fn test_applescript_chrome_background() {
+    let script = applescript("Google Chrome", "http://example.com", true).unwrap();
+    assert!(script.contains("set active tab index to previous"));
+    assert!(!script.contains("activate\n"));
+}
+
+#[test]
+fn test_applescript_not_scriptable() {
+    assert!(applescript("Firefox", "http://example.com", false).is_none());
+}
+
+#[test]
+fn test_applescript_quote() {
+    assert_eq!(
+        applescript_quote("http://x/\"} & do shell script \"rm\\"),
+        r#""http://x/\"} & do shell script \"rm\\""#
+    );
+    assert_eq!(applescript_quote("a\nb"), r#""a\nb""#);
 }
@@ -0,0 +1,187 @@
+//! Checks that the server behind a URL is up, before a browser is opened on it. This is done
+//! with a bare-bones HTTP client, so as not to pull one in as a dependency.
+
+use crate::{Error, ErrorKind, Result};
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// How long connecting, and then getting an answer, may each take
+const TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Checks that `url` can be reached, if it's an http(s) one. http URLs get a `HEAD` request,
+/// which the server has to answer with anything but a server error, whereas for https ones (as
+/// there's no TLS to be had here) the server accepting the connection has to do.
+pub(crate) fn check(url: &str) -> Result<()> {
+    let target = match Target::parse(url) {
+        Some(target) => target,
+        None => return Ok(()),
+    };
+    target
+        .check()
+        .map_err(|e| Error::new(e.kind(), format!("{} is unreachable: {}", url, e)))
+}
+
+/// Where an http(s) URL points
+#[derive(Debug, Eq, PartialEq)]
+struct Target<'a> {
+    tls: bool,
+    host: &'a str,
+    port: u16,
+    path: &'a str,
+}
+
+impl<'a> Target<'a> {
+    /// Picks `url` apart, returning `None` if it's not an http(s) URL
+    fn parse(url: &'a str) -> Option<Target<'a>> {
+        let (scheme, rest) = url.split_once("://")?;
+        let tls = match scheme.to_ascii_lowercase().as_str() {
+            "http" => false,
+            "https" => true,
+            _ => return None,
+        };
+        let rest = rest.split('#').next().unwrap_or(rest);
+        let (authority, path) = match rest.find(['/', '?']) {
+            Some(idx) => (&rest[..idx], &rest[idx..]),
+            None => (rest, "/"),
+        };
+        let host_port = authority.rsplit('@').next().unwrap_or(authority);
+        // the port comes after the last colon, unless that's within an IPv6 address
+        let (host, port) = match host_port.rfind(':') {
+            Some(idx) if !host_port[idx..].contains(']') => {
+                (&host_port[..idx], host_port[idx + 1..].parse().ok()?)
+            }
+            _ => (host_port, if tls { 443 } else { 80 }),
+        };
+        Some(Target {
+            tls,
+            host,
+            port,
+            path,
+        })
+    }
+
+    fn check(&self) -> Result<()> {
+        let host = self.host.trim_start_matches('[').trim_end_matches(']');
+        let mut last_err = Error::new(ErrorKind::NotFound, "no address found for the host");
+        let mut stream = None;
+        for addr in (host, self.port).to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, TIMEOUT) {
+                Ok(s) => {
+                    stream = Some(s);
+                    break;
+                }
+                Err(e) => last_err = e,
+            }
+        }
+        let mut stream = stream.ok_or(last_err)?;
+        if self.tls {
+            return Ok(());
+        }
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+        let slash = if self.path.starts_with('/') { "" } else { "/" };
+        write!(
+            stream,
+            "HEAD {}{} HTTP/1.1\r\nHost: {}:{}\r\nUser-Agent: webbrowser-rs\r\nConnection: close\r\n\r\n",
+            slash, self.path, self.host, self.port
+        )?;
+        // the status line is all that's needed
+        let mut head = [0u8; 64];
+        let len = stream.read(&mut head)?;
+        let status = String::from_utf8_lossy(&head[..len])
+            .lines()
+            .next()
+            .and_then(|line| line.split_whitespace().nth(1))
+            .and_then(|code| code.parse::<u16>().ok())
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "not an http server"))?;
+        if status >= 500 {
+            Err(Error::other(format!("server answered with {}", status)))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Serves one request with `response`, handing back the request once it's done
+    fn serve_once(response: &'static str) -> (u16, thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0u8; 1024];
+            let len = stream.read(&mut request).unwrap();
+            stream.write_all(response.as_bytes()).unwrap();
+            String::from_utf8_lossy(&request[..len]).into_owned()
+        });
+        (port, server)
+    }
+
+    #[test]
+    fn test_reachable() {
+        let (port, server) = serve_once("HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n");
+        check(&format!("http://127.0.0.1:{}/a/b?c=d#e", port)).unwrap();
+        let request = server.join().unwrap();
+        assert!(
+            request.starts_with("HEAD /a/b?c=d HTTP/1.1\r\n"),
+            "{}",
+            request
+        );
+    }
+
+    #[test]
+    fn test_server_error() {
+        let (port, server) = serve_once("HTTP/1.1 503 Service Unavailable\r\n\r\n");
+        let err = check(&format!("http://127.0.0.1:{}", port)).unwrap_err();
+        assert!(err.to_string().contains("503"), "{}", err);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_connection_refused() {
+        // nothing listens on a port which was just let go of
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let err = check(&format!("http://127.0.0.1:{}/", port)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ConnectionRefused);
+        assert!(err.to_string().contains("is unreachable"));
+    }
+
+    #[test]
+    fn test_only_http() {
+        assert!(check("file:///nonexistent").is_ok());
+        assert!(check("mailto:a@example.com").is_ok());
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Target::parse("https://user:pw@[::1]:8443?q").unwrap(),
+            Target {
+                tls: true,
+                host: "[::1]",
+                port: 8443,
+                path: "?q"
+            }
+        );
+        assert_eq!(
+            Target::parse("HTTP://[::1]").unwrap(),
+            Target {
+                tls: false,
+                host: "[::1]",
+                port: 80,
+                path: "/"
+            }
+        );
+        assert!(Target::parse("http://host:port/").is_none());
+    }
+}
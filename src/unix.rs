@@ -1,73 +1,2924 @@
-use crate::{Browser, Error, ErrorKind, Result};
+use crate::flags::{browser_args, Family};
+use crate::{
+    shell_quote_command, Browser, BrowserCapabilities, BrowserOptions, Error, ErrorKind,
+    OpenStatus, Result, CHROMIUM_CAPABILITIES, FIREFOX_CAPABILITIES, OPERA_CAPABILITIES,
+};
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io::Write;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::PermissionsExt;
 pub use std::os::unix::process::ExitStatusExt;
-use std::process::{Command, ExitStatus};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Output, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
 
-/// Deal with opening of browsers on Linux and *BSD - currently supports only the default browser
+/// Text browsers we know of, in order of preference. These take over the terminal, so they're
+/// always run inline.
+pub(crate) const TEXT_BROWSERS: &[&str] = &["lynx", "w3m", "elinks", "links", "links2"];
+
+/// Deal with opening of browsers on Linux and *BSD
 ///
 /// The mechanism of opening the default browser is as follows:
 /// 1. Attempt to use $BROWSER env var if available
-/// 2. Attempt to open the url via xdg-open, gvfs-open, gnome-open, open, respectively, whichever works
-///    first
+/// 2. Attempt to open the url via xdg-open, handlr, mimeo, gvfs-open, gnome-open, open,
+///    respectively, whichever works first
+///
+/// Firefox, Chrome and Opera are launched directly, if they can be found on $PATH. Browsers
+/// launched directly (these, and the ones in $BROWSER) also get the arguments in
+/// $WEBBROWSER_ARGS, which are split up with shell-like quoting.
+///
+/// `Browser::Terminal` is served by the first installed text browser, looking at $BROWSER before
+/// falling back to `TEXT_BROWSERS`.
 #[inline]
-pub fn open_browser_internal(browser: Browser, url: &str) -> Result<ExitStatus> {
-    match browser {
-        Browser::Default => open_on_unix_using_browser_env(url)
-            .or_else(|_| -> Result<ExitStatus> { Command::new("xdg-open").arg(url).status() })
-            .or_else(|r| -> Result<ExitStatus> {
-                if let Ok(desktop) = ::std::env::var("XDG_CURRENT_DESKTOP") {
-                    if desktop == "KDE" {
-                        return Command::new("kioclient").arg("exec").arg(url).status();
-                    }
-                }
-                Err(r) // If either `if` check fails, fall through to the next or_else
-            })
-            .or_else(|_| -> Result<ExitStatus> { Command::new("gvfs-open").arg(url).status() })
-            .or_else(|_| -> Result<ExitStatus> { Command::new("gnome-open").arg(url).status() })
-            .or_else(|_| -> Result<ExitStatus> { Command::new("open").arg(url).status() })
-            .or_else(|_| -> Result<ExitStatus> {
-                Command::new("kioclient").arg("exec").arg(url).status()
+pub fn open_browser_internal(
+    browser: Browser,
+    url: &str,
+    options: &BrowserOptions,
+) -> Result<OpenStatus> {
+    if options.dry_run {
+        open_with_runner(
+            &DryRunRunner::new(&OsRunner, options),
+            browser,
+            url,
+            options,
+        )
+    } else {
+        open_with_runner(&OsRunner, browser, url, options)
+    }
+}
+
+/// Same as [open_browser_internal], but handing back the browser process if it was launched
+/// directly
+#[inline]
+pub fn spawn_browser_internal(
+    browser: Browser,
+    url: &str,
+    options: &BrowserOptions,
+) -> Result<Option<Child>> {
+    if options.dry_run {
+        spawn_with_runner(
+            &DryRunRunner::new(&OsRunner, options),
+            browser,
+            url,
+            options,
+        )
+    } else {
+        spawn_with_runner(&OsRunner, browser, url, options)
+    }
+}
+
+/// Abstracts how the opener chain interacts with the OS, so that the chain can be exercised
+/// without actually launching anything
+pub(crate) trait Runner {
+    /// Returns the value of the environment variable `key`, if set
+    fn var(&self, key: &str) -> Option<String>;
+
+    /// Returns true if `program` is installed, i.e. is an executable on $PATH (or at the given
+    /// path, if it has a '/')
+    fn which(&self, program: &str) -> bool;
+
+    /// Runs `cmd` to completion, returning its exit status
+    fn status(&self, cmd: &mut Command) -> Result<ExitStatus>;
+
+    /// Runs `cmd` to completion, feeding `input` to its stdin
+    fn status_with_stdin(&self, cmd: &mut Command, input: &str) -> Result<ExitStatus>;
+
+    /// Runs `cmd` to completion, capturing its output
+    fn output(&self, cmd: &mut Command) -> Result<Output>;
+
+    /// Launches `cmd` without waiting for it to finish, returning the child process unless the
+    /// runner doesn't actually spawn anything
+    fn spawn(&self, cmd: &mut Command) -> Result<Option<Child>>;
+
+    /// Returns true if a process of `program` (matched by its file name) is running
+    fn is_running(&self, program: &str) -> bool;
+
+    /// Returns true if there's anything at `path`, be it a file or not
+    fn exists(&self, path: &str) -> bool;
+}
+
+/// The [Runner] which really spawns processes
+struct OsRunner;
+
+impl Runner for OsRunner {
+    fn var(&self, key: &str) -> Option<String> {
+        ::std::env::var(key).ok()
+    }
+
+    fn which(&self, program: &str) -> bool {
+        let is_executable = |path: &Path| {
+            path.metadata()
+                .map(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+                .unwrap_or(false)
+        };
+        if program.contains('/') {
+            return is_executable(Path::new(program));
+        }
+        ::std::env::var_os("PATH")
+            .map(|paths| {
+                ::std::env::split_paths(&paths).any(|dir| is_executable(&dir.join(program)))
             })
-            .or_else(|e| -> Result<ExitStatus> {
-                if let Ok(_child) = Command::new("x-www-browser").arg(url).spawn() {
-                    return Ok(ExitStatusExt::from_raw(0));
-                }
-                Err(e)
+            .unwrap_or(false)
+    }
+
+    fn status(&self, cmd: &mut Command) -> Result<ExitStatus> {
+        cmd.status()
+    }
+
+    fn status_with_stdin(&self, cmd: &mut Command, input: &str) -> Result<ExitStatus> {
+        let mut child = cmd.stdin(Stdio::piped()).spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            // stdin gets closed when dropped at the end of this block, so the child sees EOF
+            stdin.write_all(input.as_bytes())?;
+        }
+        child.wait()
+    }
+
+    fn output(&self, cmd: &mut Command) -> Result<Output> {
+        cmd.stdin(Stdio::null()).output()
+    }
+
+    fn spawn(&self, cmd: &mut Command) -> Result<Option<Child>> {
+        cmd.spawn().map(Some)
+    }
+
+    fn is_running(&self, program: &str) -> bool {
+        let name = Path::new(program).file_name().unwrap_or_default();
+        match fs::read_dir("/proc") {
+            Ok(entries) => entries.filter_map(|entry| entry.ok()).any(|entry| {
+                // the first nul-separated entry is the path the process was started with
+                fs::read(entry.path().join("cmdline"))
+                    .ok()
+                    .and_then(|cmdline| {
+                        let argv0 = cmdline.split(|&b| b == 0).next()?;
+                        Path::new(OsStr::from_bytes(argv0))
+                            .file_name()
+                            .map(|argv0| argv0 == name)
+                    })
+                    .unwrap_or(false)
             }),
-        _ => Err(Error::new(
-            ErrorKind::NotFound,
-            "Only the default browser is supported on this platform right now",
-        )),
-    }
-}
-
-fn open_on_unix_using_browser_env(url: &str) -> Result<ExitStatus> {
-    let browsers = ::std::env::var("BROWSER")
-        .map_err(|_| -> Error { Error::new(ErrorKind::NotFound, "BROWSER env not set") })?;
-    for browser in browsers.split(':') {
-        // $BROWSER can contain ':' delimited options, each representing a potential browser command line
-        if !browser.is_empty() {
-            // each browser command can have %s to represent URL, while %c needs to be replaced
-            // with ':' and %% with '%'
-            let cmdline = browser
-                .replace("%s", url)
-                .replace("%c", ":")
-                .replace("%%", "%");
-            let cmdarr: Vec<&str> = cmdline.split_whitespace().collect();
-            let mut cmd = Command::new(&cmdarr[0]);
-            if cmdarr.len() > 1 {
-                cmd.args(&cmdarr[1..cmdarr.len()]);
+            // no procfs here (as is usual on the BSDs), so ask pgrep instead
+            Err(_) => Command::new("pgrep")
+                .arg("-x")
+                .arg(name)
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .map(|status| status.success())
+                .unwrap_or(false),
+        }
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        Path::new(path).exists()
+    }
+}
+
+/// Same as [open_browser_internal], but giving up with `ErrorKind::TimedOut` once `deadline`
+/// passes, whichever opener is being tried at that point
+pub fn open_browser_deadline_internal(
+    browser: Browser,
+    url: &str,
+    options: &BrowserOptions,
+    deadline: Instant,
+) -> Result<OpenStatus> {
+    if options.dry_run {
+        let runner = DryRunRunner::new(&OsRunner, options);
+        open_with_runner(
+            &DeadlineRunner::new(&runner, deadline),
+            browser,
+            url,
+            options,
+        )
+    } else {
+        open_with_runner(
+            &DeadlineRunner::new(&OsRunner, deadline),
+            browser,
+            url,
+            options,
+        )
+    }
+}
+
+/// A [Runner] which refuses to start anything once the deadline has passed, and kills whatever
+/// is still running by then
+struct DeadlineRunner<'a, R: Runner> {
+    inner: &'a R,
+    deadline: Instant,
+}
+
+impl<'a, R: Runner> DeadlineRunner<'a, R> {
+    fn new(inner: &'a R, deadline: Instant) -> Self {
+        DeadlineRunner { inner, deadline }
+    }
+
+    fn check(&self) -> Result<()> {
+        if Instant::now() < self.deadline {
+            Ok(())
+        } else {
+            Err(Error::new(
+                ErrorKind::TimedOut,
+                "gave up, as the deadline for opening the url passed",
+            ))
+        }
+    }
+
+    /// Waits for `child` to exit, unless it takes until the deadline
+    fn wait(&self, mut child: Child) -> Result<ExitStatus> {
+        loop {
+            if let Some(status) = child.try_wait()? {
+                return Ok(status);
             }
-            if !browser.contains("%s") {
-                // append the url as an argument only if it was not already set via %s
-                cmd.arg(url);
+            if let Err(e) = self.check() {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(e);
             }
-            if let Ok(status) = cmd.status() {
-                return Ok(status);
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
+
+impl<'a, R: Runner> Runner for DeadlineRunner<'a, R> {
+    fn var(&self, key: &str) -> Option<String> {
+        self.inner.var(key)
+    }
+
+    fn which(&self, program: &str) -> bool {
+        self.inner.which(program)
+    }
+
+    fn status(&self, cmd: &mut Command) -> Result<ExitStatus> {
+        self.check()?;
+        // spawned rather than run, so that it can be cut short
+        let status = match self.inner.spawn(cmd)? {
+            Some(child) => self.wait(child)?,
+            None => ExitStatus::from_raw(0),
+        };
+        self.check().map(|_| status)
+    }
+
+    fn status_with_stdin(&self, cmd: &mut Command, input: &str) -> Result<ExitStatus> {
+        self.check()?;
+        let mut child = match self.inner.spawn(cmd.stdin(Stdio::piped()))? {
+            Some(child) => child,
+            None => return self.check().map(|_| ExitStatus::from_raw(0)),
+        };
+        // written from a thread, so that a child which doesn't read it can't hold us up
+        let writer = child.stdin.take().map(|mut stdin| {
+            let input = input.to_string();
+            thread::spawn(move || stdin.write_all(input.as_bytes()))
+        });
+        let status = self.wait(child)?;
+        if let Some(writer) = writer {
+            writer
+                .join()
+                .unwrap_or_else(|_| Err(Error::other("couldn't write the url to stdin")))?;
+        }
+        self.check().map(|_| status)
+    }
+
+    fn output(&self, cmd: &mut Command) -> Result<Output> {
+        self.check()?;
+        self.inner.output(cmd)
+    }
+
+    fn spawn(&self, cmd: &mut Command) -> Result<Option<Child>> {
+        self.check()?;
+        self.inner.spawn(cmd)
+    }
+
+    fn is_running(&self, program: &str) -> bool {
+        self.inner.is_running(program)
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.inner.exists(path)
+    }
+}
+
+/// Same as [open_browser_internal], but trying every opener rather than stopping at the first one
+/// which works, and returning how each of them went
+pub fn open_browser_report_internal(
+    browser: Browser,
+    url: &str,
+    options: &BrowserOptions,
+) -> Vec<(String, Result<OpenStatus>)> {
+    if options.dry_run {
+        report_with_runner(
+            &DryRunRunner::new(&OsRunner, options),
+            browser,
+            url,
+            options,
+        )
+    } else {
+        report_with_runner(&OsRunner, browser, url, options)
+    }
+}
+
+fn report_with_runner(
+    runner: &impl Runner,
+    browser: Browser,
+    url: &str,
+    options: &BrowserOptions,
+) -> Vec<(String, Result<OpenStatus>)> {
+    let runner = ReportRunner {
+        inner: runner,
+        report: RefCell::new(Vec::new()),
+    };
+    // every command fails as far as the chain can tell, so the outcome is always an error
+    let _ = open_with_runner(&runner, browser, url, options);
+    runner.report.into_inner()
+}
+
+/// A [Runner] which records how each command went, but then tells the caller it wasn't found, so
+/// that the whole chain gets walked
+struct ReportRunner<'a, R: Runner> {
+    inner: &'a R,
+    report: RefCell<Vec<(String, Result<OpenStatus>)>>,
+}
+
+impl<'a, R: Runner> ReportRunner<'a, R> {
+    fn record<T>(&self, cmd: &Command, result: Result<OpenStatus>) -> Result<T> {
+        let (program, args) = crate::command_line(cmd);
+        self.report
+            .borrow_mut()
+            .push((shell_quote_command(&program, &args), result));
+        Err(Error::new(
+            ErrorKind::NotFound,
+            "moving on to the next opener",
+        ))
+    }
+}
+
+impl<'a, R: Runner> Runner for ReportRunner<'a, R> {
+    fn var(&self, key: &str) -> Option<String> {
+        self.inner.var(key)
+    }
+
+    fn which(&self, program: &str) -> bool {
+        self.inner.which(program)
+    }
+
+    fn status(&self, cmd: &mut Command) -> Result<ExitStatus> {
+        let result = self.inner.status(cmd).map(OpenStatus::from);
+        self.record(cmd, result)
+    }
+
+    fn status_with_stdin(&self, cmd: &mut Command, input: &str) -> Result<ExitStatus> {
+        let result = self
+            .inner
+            .status_with_stdin(cmd, input)
+            .map(OpenStatus::from);
+        self.record(cmd, result)
+    }
+
+    fn output(&self, cmd: &mut Command) -> Result<Output> {
+        self.inner.output(cmd)
+    }
+
+    fn spawn(&self, cmd: &mut Command) -> Result<Option<Child>> {
+        let result = self.inner.spawn(cmd).map(|_| OpenStatus::launched());
+        self.record(cmd, result)
+    }
+
+    fn is_running(&self, program: &str) -> bool {
+        self.inner.is_running(program)
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.inner.exists(path)
+    }
+}
+
+/// A [Runner] which reports commands to the dry-run sink instead of running them. Commands whose
+/// program is installed are treated as having succeeded, and the rest as not found, so that the
+/// chain gets walked the same way it would for real.
+struct DryRunRunner<'a, R: Runner> {
+    inner: &'a R,
+    options: &'a BrowserOptions,
+}
+
+impl<'a, R: Runner> DryRunRunner<'a, R> {
+    fn new(inner: &'a R, options: &'a BrowserOptions) -> Self {
+        DryRunRunner { inner, options }
+    }
+
+    fn report(&self, cmd: &Command) -> Result<()> {
+        let (program, args) = crate::command_line(cmd);
+        self.options.report_dry_run(&program, &args);
+        if self.inner.which(&program) {
+            Ok(())
+        } else {
+            Err(Error::new(
+                ErrorKind::NotFound,
+                format!("{} not found", program),
+            ))
+        }
+    }
+}
+
+impl<'a, R: Runner> Runner for DryRunRunner<'a, R> {
+    fn var(&self, key: &str) -> Option<String> {
+        self.inner.var(key)
+    }
+
+    fn which(&self, program: &str) -> bool {
+        self.inner.which(program)
+    }
+
+    fn status(&self, cmd: &mut Command) -> Result<ExitStatus> {
+        self.report(cmd).map(|_| ExitStatus::from_raw(0))
+    }
+
+    fn status_with_stdin(&self, cmd: &mut Command, _input: &str) -> Result<ExitStatus> {
+        self.status(cmd)
+    }
+
+    fn output(&self, _cmd: &mut Command) -> Result<Output> {
+        // probes aren't reported, and nothing conclusive comes out of them
+        Err(Error::other("dry run"))
+    }
+
+    fn spawn(&self, cmd: &mut Command) -> Result<Option<Child>> {
+        self.report(cmd).map(|_| None)
+    }
+
+    fn is_running(&self, program: &str) -> bool {
+        self.inner.is_running(program)
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.inner.exists(path)
+    }
+}
+
+/// Stands in for the url while resolving the opener, so that where it goes can be told apart
+const RESOLVE_URL: &str = "https://webbrowser-rs.invalid/resolve";
+
+/// The command which the chain settled on for a browser, with which urls can be opened later on
+/// without walking the chain (and probing for programs) again
+#[derive(Debug, Clone)]
+pub struct ResolvedCommand {
+    program: String,
+    /// The arguments, with [RESOLVE_URL] in place of the url
+    args: Vec<String>,
+    envs: Vec<(OsString, Option<OsString>)>,
+    run: RunMode,
+}
+
+/// How a [ResolvedCommand] gets run
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum RunMode {
+    Status,
+    StatusWithStdin,
+    Spawn,
+}
+
+impl ResolvedCommand {
+    /// Returns the command which opens `url`
+    fn command(&self, url: &str) -> Command {
+        let mut cmd = Command::new(&self.program);
+        for arg in &self.args {
+            // the url went through the same quoting as for the opener which took it
+            cmd.arg(
+                arg.replace(&powershell_quote(RESOLVE_URL), &powershell_quote(url))
+                    .replace(RESOLVE_URL, url),
+            );
+        }
+        for (key, value) in &self.envs {
+            match value {
+                Some(value) => cmd.env(key, value),
+                None => cmd.env_remove(key),
+            };
+        }
+        cmd
+    }
+}
+
+/// Works out the command which [open_browser_internal] would use to open urls in `browser`
+pub fn resolve_opener_internal(
+    browser: Browser,
+    options: &BrowserOptions,
+) -> Result<ResolvedCommand> {
+    resolve_with_runner(&OsRunner, browser, options)
+}
+
+fn resolve_with_runner(
+    runner: &impl Runner,
+    browser: Browser,
+    options: &BrowserOptions,
+) -> Result<ResolvedCommand> {
+    let runner = ResolveRunner {
+        inner: runner,
+        resolved: RefCell::new(None),
+    };
+    open_with_runner(&runner, browser, RESOLVE_URL, options).and_then(OpenStatus::into_result)?;
+    runner
+        .resolved
+        .into_inner()
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "No opener found"))
+}
+
+/// Opens `url` with a command from [resolve_opener_internal]
+pub fn open_resolved_internal(
+    resolved: &ResolvedCommand,
+    url: &str,
+    options: &BrowserOptions,
+) -> Result<OpenStatus> {
+    if options.dry_run {
+        open_resolved_with_runner(&DryRunRunner::new(&OsRunner, options), resolved, url)
+    } else {
+        open_resolved_with_runner(&OsRunner, resolved, url)
+    }
+}
+
+fn open_resolved_with_runner(
+    runner: &impl Runner,
+    resolved: &ResolvedCommand,
+    url: &str,
+) -> Result<OpenStatus> {
+    let mut cmd = resolved.command(url);
+    match resolved.run {
+        RunMode::Status => runner.status(&mut cmd).map(OpenStatus::from),
+        RunMode::StatusWithStdin => runner
+            .status_with_stdin(&mut cmd, url)
+            .map(OpenStatus::from),
+        RunMode::Spawn => runner.spawn(&mut cmd).map(|_| OpenStatus::launched()),
+    }
+}
+
+/// A [Runner] which takes the first command whose program is installed to be the one which works
+/// out, and keeps it rather than running it
+struct ResolveRunner<'a, R: Runner> {
+    inner: &'a R,
+    resolved: RefCell<Option<ResolvedCommand>>,
+}
+
+impl<'a, R: Runner> ResolveRunner<'a, R> {
+    fn resolve(&self, cmd: &Command, run: RunMode) -> Result<()> {
+        let (program, args) = crate::command_line(cmd);
+        if !self.inner.which(&program) {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("{} not found", program),
+            ));
+        }
+        let envs = cmd
+            .get_envs()
+            .map(|(key, value)| (key.to_owned(), value.map(OsStr::to_owned)))
+            .collect();
+        *self.resolved.borrow_mut() = Some(ResolvedCommand {
+            program,
+            args,
+            envs,
+            run,
+        });
+        Ok(())
+    }
+}
+
+impl<'a, R: Runner> Runner for ResolveRunner<'a, R> {
+    fn var(&self, key: &str) -> Option<String> {
+        self.inner.var(key)
+    }
+
+    fn which(&self, program: &str) -> bool {
+        self.inner.which(program)
+    }
+
+    fn status(&self, cmd: &mut Command) -> Result<ExitStatus> {
+        self.resolve(cmd, RunMode::Status)
+            .map(|_| ExitStatus::from_raw(0))
+    }
+
+    fn status_with_stdin(&self, cmd: &mut Command, _input: &str) -> Result<ExitStatus> {
+        self.resolve(cmd, RunMode::StatusWithStdin)
+            .map(|_| ExitStatus::from_raw(0))
+    }
+
+    fn output(&self, cmd: &mut Command) -> Result<Output> {
+        // probes are run for real, as the answer is needed to get the chain right
+        self.inner.output(cmd)
+    }
+
+    fn spawn(&self, cmd: &mut Command) -> Result<Option<Child>> {
+        self.resolve(cmd, RunMode::Spawn).map(|_| None)
+    }
+
+    fn is_running(&self, program: &str) -> bool {
+        self.inner.is_running(program)
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.inner.exists(path)
+    }
+}
+
+fn spawn_with_runner(
+    runner: &impl Runner,
+    browser: Browser,
+    url: &str,
+    options: &BrowserOptions,
+) -> Result<Option<Child>> {
+    match browser {
+        Browser::Firefox | Browser::Chrome | Browser::Opera | Browser::WebPositive => {
+            spawn_named_browser(runner, browser, url, options)
+        }
+        _ => open_with_runner(runner, browser, url, options)
+            .and_then(OpenStatus::into_result)
+            .map(|_| None),
+    }
+}
+
+fn open_with_runner(
+    runner: &impl Runner,
+    browser: Browser,
+    url: &str,
+    options: &BrowserOptions,
+) -> Result<OpenStatus> {
+    match browser {
+        Browser::Default if !options.raw && crate::has_custom_scheme(url) => {
+            // $BROWSER is for browsers, so it's no good for the likes of steam: and zoommtg:
+            let mut attempts = Attempts::new(options);
+            open_with_system_openers(runner, url, options, &mut attempts)
+                .or_else(|_| attempts.into_result(url))
+        }
+        Browser::Default if options.incognito => {
+            // none of the generic openers can do incognito, so it's up to $BROWSER
+            let mut attempts = Attempts::new(options);
+            open_on_unix_using_browser_env(runner, url, options, &mut attempts)
+                .or_else(|e| attempts.first_status.ok_or(e))
+                .map_err(|e| {
+                    if e.kind() == ErrorKind::NotFound {
+                        Error::new(
+                            ErrorKind::Unsupported,
+                            "incognito mode is not supported for the default browser",
+                        )
+                    } else {
+                        e
+                    }
+                })
+        }
+        Browser::Default => {
+            let mut attempts = Attempts::new(options);
+            open_on_unix_using_browser_env(runner, url, options, &mut attempts)
+                .or_else(|_| open_with_system_openers(runner, url, options, &mut attempts))
+                .or_else(|_| attempts.into_result(url))
+        }
+        Browser::Firefox | Browser::Chrome | Browser::Opera | Browser::WebPositive => {
+            spawn_named_browser(runner, browser, url, options).map(|_| OpenStatus::launched())
+        }
+        Browser::Terminal => open_in_text_browser(runner, url, options),
+        _ => Err(crate::unsupported_browser(browser)),
+    }
+}
+
+/// Returns true if there's something installed which can open `browser`
+pub fn is_available_internal(browser: Browser) -> bool {
+    is_available_with_runner(&OsRunner, browser)
+}
+
+fn is_available_with_runner(runner: &impl Runner, browser: Browser) -> bool {
+    let program_of = |entry: &str| entry.split_whitespace().next().map(String::from);
+    match browser {
+        Browser::Default => {
+            browser_env_entries(runner)
+                .iter()
+                .filter_map(|entry| program_of(entry))
+                .any(|program| runner.which(&program))
+                || system_openers(runner, &BrowserOptions::default())
+                    .iter()
+                    .any(|opener| runner.which(opener.program))
+        }
+        Browser::Terminal => {
+            browser_env_entries(runner)
+                .iter()
+                .filter_map(|entry| program_of(entry))
+                .any(|program| is_text_browser(&program) && runner.which(&program))
+                || TEXT_BROWSERS.iter().any(|program| runner.which(program))
+        }
+        _ => browser_commands(browser)
+            .iter()
+            .any(|program| runner.which(program)),
+    }
+}
+
+/// Returns the version of `browser`, as reported by its `--version`
+pub fn browser_version_internal(browser: Browser) -> Result<String> {
+    version_with_runner(&OsRunner, browser)
+}
+
+fn version_with_runner(runner: &impl Runner, browser: Browser) -> Result<String> {
+    let candidates: Vec<String> = match browser {
+        // the closest thing to the default browser which can be told is $BROWSER
+        Browser::Default => browser_env_entries(runner)
+            .iter()
+            .filter_map(|entry| entry.split_whitespace().next().map(String::from))
+            .collect(),
+        Browser::Terminal => {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "text browsers don't have a common way of telling their version",
+            ))
+        }
+        _ => browser_commands(browser)
+            .iter()
+            .map(|program| program.to_string())
+            .collect(),
+    };
+    let program = candidates
+        .into_iter()
+        .find(|program| runner.which(program))
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("{} is not installed", browser)))?;
+    let output = runner.output(Command::new(&program).arg("--version"))?;
+    crate::parse_version(&String::from_utf8_lossy(&output.stdout))
+        .ok_or_else(|| Error::other(format!("couldn't make out the version of {}", program)))
+}
+
+/// Opens `url` with the application whose desktop entry is `desktop_id`, via `gtk-launch` if it's
+/// installed, or else by running the `Exec` line of the entry ourselves
+pub fn open_desktop_app_internal(
+    desktop_id: &str,
+    url: &str,
+    options: &BrowserOptions,
+) -> Result<OpenStatus> {
+    if options.dry_run {
+        desktop_app_with_runner(
+            &DryRunRunner::new(&OsRunner, options),
+            desktop_id,
+            url,
+            options,
+        )
+    } else {
+        desktop_app_with_runner(&OsRunner, desktop_id, url, options)
+    }
+}
+
+fn desktop_app_with_runner(
+    runner: &impl Runner,
+    desktop_id: &str,
+    url: &str,
+    options: &BrowserOptions,
+) -> Result<OpenStatus> {
+    if desktop_id.contains('/') {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("{} is not a desktop id", desktop_id),
+        ));
+    }
+    let desktop_id = if desktop_id.ends_with(".desktop") {
+        desktop_id.to_string()
+    } else {
+        format!("{}.desktop", desktop_id)
+    };
+    let path = find_desktop_file(runner, &desktop_id).ok_or_else(|| {
+        Error::new(
+            ErrorKind::NotFound,
+            format!("no application with desktop id {}", desktop_id),
+        )
+    })?;
+    if runner.which("gtk-launch") {
+        // which takes care of everything the entry asks for, e.g. DBusActivatable
+        let mut cmd = Command::new("gtk-launch");
+        cmd.arg(&desktop_id).arg(url);
+        let mut cmd = launch_command(runner, cmd, options, false);
+        return runner.status(&mut cmd).map(OpenStatus::from);
+    }
+    let argv = fs::read_to_string(&path)
+        .ok()
+        .and_then(|entry| desktop_exec(&entry))
+        .map(|exec| exec_args(&exec, url))
+        .filter(|argv| !argv.is_empty())
+        .ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("no command to run in {}", path.display()),
+            )
+        })?;
+    let mut cmd = Command::new(&argv[0]);
+    cmd.args(&argv[1..]);
+    let mut cmd = launch_command(runner, cmd, options, true);
+    runner.spawn(&mut cmd).map(|_| OpenStatus::launched())
+}
+
+/// Opens `url` by running the command `template`, which is split up into words with shell-like
+/// quoting, and gets the url substituted in for %s or %u (with the other escapes being the same
+/// as for $BROWSER). The url is appended if there's no placeholder for it.
+pub fn open_with_template_internal(
+    template: &str,
+    url: &str,
+    options: &BrowserOptions,
+) -> Result<OpenStatus> {
+    if options.dry_run {
+        template_with_runner(
+            &DryRunRunner::new(&OsRunner, options),
+            template,
+            url,
+            options,
+        )
+    } else {
+        template_with_runner(&OsRunner, template, url, options)
+    }
+}
+
+fn template_with_runner(
+    runner: &impl Runner,
+    template: &str,
+    url: &str,
+    options: &BrowserOptions,
+) -> Result<OpenStatus> {
+    let cmd = template_command(template, url)?;
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    let mut cmd = launch_command(runner, cmd, options, true);
+    run_opener(runner, &mut cmd, &program, options)
+}
+
+/// Builds the command to run for a template given to [open_with_template_internal]
+fn template_command(template: &str, url: &str) -> Result<Command> {
+    let mut has_url = false;
+    let words: Vec<String> = split_args(template)
+        .iter()
+        .map(|word| {
+            let (word, substituted) = expand_escapes(word, url, &['s', 'u']);
+            has_url |= substituted;
+            word
+        })
+        .collect();
+    let (program, args) = words
+        .split_first()
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "the command template is empty"))?;
+    let mut cmd = Command::new(program);
+    cmd.args(args);
+    if !has_url {
+        cmd.arg(url);
+    }
+    Ok(cmd)
+}
+
+/// Returns the path of the desktop entry file for `desktop_id`, looking in the `applications`
+/// dir of each of the XDG data dirs, in order of precedence
+fn find_desktop_file(runner: &impl Runner, desktop_id: &str) -> Option<PathBuf> {
+    let data_home = runner
+        .var("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .or_else(|| {
+            runner
+                .var("HOME")
+                .map(|home| format!("{}/.local/share", home))
+        });
+    let data_dirs = runner
+        .var("XDG_DATA_DIRS")
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_string());
+    data_home
+        .into_iter()
+        .chain(data_dirs.split(':').map(String::from))
+        .map(|dir| Path::new(&dir).join("applications").join(desktop_id))
+        .find(|path| path.is_file())
+}
+
+/// Returns the `Exec` value from the `[Desktop Entry]` group of a desktop entry
+fn desktop_exec(entry: &str) -> Option<String> {
+    let mut in_group = false;
+    for line in entry.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_group = line == "[Desktop Entry]";
+        } else if in_group {
+            match line.split_once('=') {
+                Some((key, value)) if key.trim() == "Exec" => {
+                    return Some(value.trim().to_string())
+                }
+                _ => {}
             }
         }
     }
-    Err(Error::new(
-        ErrorKind::NotFound,
-        "No valid command in $BROWSER",
-    ))
+    None
+}
+
+/// Turns the `Exec` value of a desktop entry into the command line which opens `url`, expanding
+/// its field codes. The url is appended if there's no field code for it.
+fn exec_args(exec: &str, url: &str) -> Vec<String> {
+    let mut has_url = false;
+    let mut argv = Vec::new();
+    for word in split_args(exec) {
+        match word.as_str() {
+            "%u" | "%U" | "%f" | "%F" => {
+                argv.push(url.to_string());
+                has_url = true;
+            }
+            // the icon, name and location of the entry, and deprecated codes, none of which
+            // browsers need
+            "%i" | "%c" | "%k" | "%d" | "%D" | "%n" | "%N" | "%v" | "%m" => {}
+            _ => argv.push(word.replace("%%", "%")),
+        }
+    }
+    if !has_url && !argv.is_empty() {
+        argv.push(url.to_string());
+    }
+    argv
+}
+
+/// Returns the capabilities of the default browser. Only $BROWSER gets to use any of them, so
+/// they're those of the first entry there which we recognise.
+pub(crate) fn default_capabilities() -> BrowserCapabilities {
+    default_capabilities_with_runner(&OsRunner)
+}
+
+fn default_capabilities_with_runner(runner: &impl Runner) -> BrowserCapabilities {
+    browser_env_entries(runner)
+        .iter()
+        .filter_map(|entry| entry.split_whitespace().next())
+        .find_map(Family::from_command)
+        .map(|family| match family {
+            Family::Firefox => FIREFOX_CAPABILITIES,
+            Family::Chromium | Family::Edge => CHROMIUM_CAPABILITIES,
+            Family::Opera => OPERA_CAPABILITIES,
+        })
+        .unwrap_or_default()
+}
+
+/// A generic opener, tried if $BROWSER doesn't work out
+#[derive(Debug, Clone)]
+struct Opener {
+    program: &'static str,
+    args: &'static [&'static str],
+    /// Whether to launch it without waiting, as it's a browser rather than a launcher
+    detached: bool,
+    /// Turns the url into the last argument
+    url_arg: fn(&str) -> String,
+}
+
+impl Opener {
+    const fn new(program: &'static str, args: &'static [&'static str]) -> Self {
+        Opener {
+            program,
+            args,
+            detached: false,
+            url_arg: str::to_owned,
+        }
+    }
+
+    fn command(&self, url: &str) -> Command {
+        let mut cmd = Command::new(self.program);
+        cmd.args(self.args).arg((self.url_arg)(url));
+        cmd
+    }
+}
+
+/// Returns the generic openers, in the order in which they should be tried
+fn system_openers(runner: &impl Runner, options: &BrowserOptions) -> Vec<Opener> {
+    let mut openers = vec![Opener::new("xdg-open", &[])];
+    if runner.var("XDG_CURRENT_DESKTOP").as_deref() == Some("KDE") {
+        openers.push(Opener::new("kioclient", &["exec"]));
+    }
+    openers.extend_from_slice(&[
+        Opener::new("handlr", &["open"]),
+        Opener::new("mimeo", &[]),
+        Opener::new("gvfs-open", &[]),
+        Opener::new("gnome-open", &[]),
+        Opener::new("open", &[]),
+        Opener::new("kioclient", &["exec"]),
+        Opener {
+            detached: true,
+            ..Opener::new("x-www-browser", &[])
+        },
+    ]);
+    if !BARE_OPEN_IS_OPENER {
+        openers.retain(|opener| opener.program != "open");
+    }
+    if let Some(handler) = crostini_url_handler(runner) {
+        openers.insert(0, Opener::new(handler, &[]));
+    }
+    if is_wsl(runner) {
+        if options.prefer_host_browser {
+            openers.splice(0..0, WSL_HOST_OPENERS.iter().cloned());
+        } else {
+            openers.extend_from_slice(WSL_HOST_OPENERS);
+        }
+    }
+    // a program which failed once would only fail the same way again, costing another spawn
+    let mut seen = HashSet::new();
+    openers.retain(|opener| seen.insert(opener.program));
+    openers
+}
+
+/// Whether a bare `open` on $PATH can be taken to be a url opener. On Linux it's usually `openvt`
+/// (under its old name), or some macOS look-alike shim, neither of which should be handed a url.
+const BARE_OPEN_IS_OPENER: bool = cfg!(not(target_os = "linux"));
+
+/// Openers which hand the url over to the Windows host under WSL
+const WSL_HOST_OPENERS: &[Opener] = &[
+    Opener::new("wslview", &[]),
+    Opener {
+        url_arg: powershell_quote,
+        ..Opener::new(
+            "powershell.exe",
+            &["-NoProfile", "-NonInteractive", "-Command", "Start-Process"],
+        )
+    },
+];
+
+/// Quotes the url as a PowerShell string literal, so that characters like `&` survive
+fn powershell_quote(url: &str) -> String {
+    format!("'{}'", url.replace('\'', "''"))
+}
+
+/// Returns true if running under the Windows Subsystem for Linux, which sets these variables for
+/// every process it starts
+fn is_wsl(runner: &impl Runner) -> bool {
+    runner.var("WSL_DISTRO_NAME").is_some() || runner.var("WSL_INTEROP").is_some()
+}
+
+/// Which only exists in a Crostini container, telling the version of Chrome OS
+const CROS_MILESTONE: &str = "/dev/.cros_milestone";
+
+/// Where Crostini (Linux on Chrome OS) keeps the program handing urls over to the Chrome OS browser
+const GARCON_URL_HANDLER: &str = "/opt/google/cros-containers/bin/garcon-url-handler";
+
+/// Returns the program handing urls over to Chrome OS, if running in a Crostini container. It's
+/// what xdg-open is wired up to there, but going to it directly works even if xdg-open is missing.
+fn crostini_url_handler(runner: &impl Runner) -> Option<&'static str> {
+    if runner.which("garcon-url-handler") {
+        Some("garcon-url-handler")
+    } else if runner.exists(CROS_MILESTONE) {
+        Some(GARCON_URL_HANDLER)
+    } else {
+        None
+    }
+}
+
+/// Tries each of the generic openers in turn, until one works
+fn open_with_system_openers(
+    runner: &impl Runner,
+    url: &str,
+    options: &BrowserOptions,
+    attempts: &mut Attempts,
+) -> Result<OpenStatus> {
+    for opener in system_openers(runner, options) {
+        if attempts.stopped {
+            break;
+        }
+        if attempts.has_tried(opener.program) {
+            // e.g. if $BROWSER is set to xdg-open
+            continue;
+        }
+        if opener.program == "xdg-open" && xdg_has_no_handler(runner, url) {
+            attempts.failed(
+                "xdg-open",
+                Error::new(ErrorKind::NotFound, "no handler for the url"),
+            );
+            continue;
+        }
+        let mut cmd = launch_command(runner, opener.command(url), options, false);
+        let result = if opener.detached {
+            runner.spawn(&mut cmd).map(|_| OpenStatus::launched())
+        } else {
+            run_opener(runner, &mut cmd, opener.program, options)
+        };
+        if let Some(status) = attempts.ran(opener.program, result) {
+            return Ok(status);
+        }
+    }
+    Err(Error::new(ErrorKind::NotFound, "No opener found"))
+}
+
+/// Runs `cmd` (of `program`, be it an opener or a browser) to completion if the options ask for
+/// [blocking](crate::BrowserOptions::blocking), or else just launches it. Text browsers always run
+/// to completion, as they take over the terminal.
+fn run_opener(
+    runner: &impl Runner,
+    cmd: &mut Command,
+    program: &str,
+    options: &BrowserOptions,
+) -> Result<OpenStatus> {
+    if options.blocking || is_text_browser(program) {
+        runner.status(cmd).map(OpenStatus::from)
+    } else {
+        runner.spawn(cmd).map(|_| OpenStatus::launched())
+    }
+}
+
+/// Keeps track of the openers which failed, to come up with the most telling error at the end
+#[derive(Default)]
+struct Attempts {
+    tried: Vec<String>,
+    first_meaningful: Option<Error>,
+    /// The status of the first opener which ran, but exited unsuccessfully
+    first_status: Option<OpenStatus>,
+    /// Whether to stop at the first opener which is there, but doesn't work out
+    strict: bool,
+    /// Set once there's no point in trying any more openers
+    stopped: bool,
+}
+
+impl Attempts {
+    fn new(options: &BrowserOptions) -> Self {
+        Attempts {
+            strict: options.strict,
+            ..Default::default()
+        }
+    }
+
+    /// Records how running `program` went, handing back the status if it worked out
+    fn ran(&mut self, program: &str, result: Result<OpenStatus>) -> Option<OpenStatus> {
+        match result {
+            Ok(status) if status.success() => return Some(status),
+            Ok(status) => {
+                log::debug!("{} exited with {:?}", program, status.code());
+                self.first_status.get_or_insert(status);
+                self.tried.push(program.to_string());
+            }
+            Err(e) => {
+                let missing = e.kind() == ErrorKind::NotFound;
+                self.failed(program, e);
+                if missing {
+                    return None;
+                }
+            }
+        }
+        // it's there, but didn't work out
+        self.stopped = self.strict;
+        None
+    }
+
+    /// Records that `program` failed with `err`
+    fn failed(&mut self, program: &str, err: Error) {
+        log::debug!("{} failed: {}", program, err);
+        // not being installed is par for the course, but anything else is worth knowing about
+        if err.kind() != ErrorKind::NotFound && self.first_meaningful.is_none() {
+            self.first_meaningful = Some(Error::new(err.kind(), format!("{}: {}", program, err)));
+        }
+        self.tried.push(program.to_string());
+    }
+
+    /// Returns true if `program` was tried already
+    fn has_tried(&self, program: &str) -> bool {
+        self.tried.iter().any(|tried| tried == program)
+    }
+
+    /// Returns the status of the first opener which ran (if none succeeded), or else the error
+    /// from [into_error](Attempts::into_error)
+    fn into_result(self, url: &str) -> Result<OpenStatus> {
+        match self.first_status {
+            Some(status) => Ok(status),
+            None => Err(self.into_error(url)),
+        }
+    }
+
+    /// Returns the first error which wasn't just a missing opener, or else a summary of what was
+    /// tried
+    fn into_error(self, url: &str) -> Error {
+        let Attempts {
+            tried,
+            first_meaningful,
+            ..
+        } = self;
+        let err = first_meaningful.unwrap_or_else(|| {
+            Error::new(
+                ErrorKind::NotFound,
+                format!("no opener found (tried {})", tried.join(", ")),
+            )
+        });
+        Error::new(
+            err.kind(),
+            format!(
+                "{}, to open it by hand, run: {}",
+                err,
+                shell_quote_command("xdg-open", &[url])
+            ),
+        )
+    }
+}
+
+/// Commands, in order of preference, which launch `browser` directly
+fn browser_commands(browser: Browser) -> &'static [&'static str] {
+    match browser {
+        Browser::Firefox => &["firefox"],
+        Browser::Chrome => &[
+            "google-chrome",
+            "google-chrome-stable",
+            "chromium",
+            "chromium-browser",
+        ],
+        Browser::Opera => &["opera"],
+        Browser::WebPositive => &["WebPositive"],
+        _ => &[],
+    }
+}
+
+/// Launches the first of `browser`'s commands which can be found, without waiting for it
+fn spawn_named_browser(
+    runner: &impl Runner,
+    browser: Browser,
+    url: &str,
+    options: &BrowserOptions,
+) -> Result<Option<Child>> {
+    for program in browser_commands(browser) {
+        let args = browser_args(
+            program,
+            options,
+            || runner.is_running(program),
+            |key| runner.var(key),
+        )?;
+        let mut cmd = Command::new(program);
+        cmd.args(webbrowser_args(runner)).args(&args).arg(url);
+        let mut cmd = launch_command(runner, cmd, options, true);
+        match runner.spawn(&mut cmd) {
+            Ok(child) => return Ok(child),
+            Err(ref e) if e.kind() == ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Err(Error::new(
+        ErrorKind::NotFound,
+        format!("{} not found", browser),
+    ))
+}
+
+/// Returns true if xdg-mime positively reports that there's no handler registered for the url's
+/// scheme. Some versions of xdg-open exit with 0 in that case without opening anything, so we'd
+/// rather move on down the chain. If xdg-mime can't tell us either way, we trust xdg-open.
+fn xdg_has_no_handler(runner: &impl Runner, url: &str) -> bool {
+    let scheme = match crate::url_scheme(url) {
+        Some(scheme) => scheme,
+        None => return false,
+    };
+    if scheme == "file" {
+        // files are dispatched by mime type, not by scheme
+        return false;
+    }
+    let mut cmd = Command::new("xdg-mime");
+    cmd.arg("query")
+        .arg("default")
+        .arg(format!("x-scheme-handler/{}", scheme));
+    match runner.output(&mut cmd) {
+        Ok(output) => output.status.success() && output.stdout.iter().all(u8::is_ascii_whitespace),
+        Err(_) => false,
+    }
+}
+
+/// Returns true if `cmd` (a bare name, or a path) refers to one of the known `TEXT_BROWSERS`
+pub(crate) fn is_text_browser(cmd: &str) -> bool {
+    Path::new(cmd)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| TEXT_BROWSERS.contains(&name))
+        .unwrap_or(false)
+}
+
+/// Opens the url inline in a text browser, preferring the ones listed in $BROWSER
+fn open_in_text_browser(
+    runner: &impl Runner,
+    url: &str,
+    options: &BrowserOptions,
+) -> Result<OpenStatus> {
+    let from_env = browser_env_entries(runner)
+        .into_iter()
+        .filter(|browser| {
+            browser
+                .split_whitespace()
+                .next()
+                .map(is_text_browser)
+                .unwrap_or(false)
+        })
+        .map(|browser| browser_env_command(&browser, url, &[], false));
+    let from_list = TEXT_BROWSERS.iter().map(|browser| {
+        let mut cmd = Command::new(browser);
+        cmd.arg(url);
+        cmd
+    });
+    for mut cmd in from_env.chain(from_list) {
+        apply_env(&mut cmd, options);
+        match runner.status(&mut cmd) {
+            Err(ref e) if e.kind() == ErrorKind::NotFound => continue,
+            result => return result.map(OpenStatus::from),
+        }
+    }
+    Err(Error::new(ErrorKind::NotFound, "No text browser found"))
+}
+
+/// Returns the non-empty entries of $BROWSER. It can contain ':' delimited options, each
+/// representing a potential browser command line.
+fn browser_env_entries(runner: &impl Runner) -> Vec<String> {
+    runner
+        .var("BROWSER")
+        .map(|browsers| {
+            browsers
+                .split(':')
+                .filter(|browser| !browser.is_empty())
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Expands the escapes in a $BROWSER argument in a single left-to-right pass, so that the output of
+/// one never gets expanded again: %s becomes the url, %c becomes ':' and %% becomes '%'. Any other
+/// % sequence is kept as is. Also returns whether the url was substituted in.
+fn expand_browser_escapes(arg: &str, url: &str) -> (String, bool) {
+    expand_escapes(arg, url, &['s'])
+}
+
+/// Same as [expand_browser_escapes], but with each of `url_escapes` (instead of just %s) becoming
+/// the url
+fn expand_escapes(arg: &str, url: &str, url_escapes: &[char]) -> (String, bool) {
+    let mut expanded = String::with_capacity(arg.len());
+    let mut substituted = false;
+    let mut chars = arg.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            expanded.push(c);
+            continue;
+        }
+        match chars.peek() {
+            Some(c) if url_escapes.contains(c) => {
+                expanded.push_str(url);
+                substituted = true;
+            }
+            Some('c') => expanded.push(':'),
+            Some('%') => expanded.push('%'),
+            _ => {
+                expanded.push('%');
+                continue;
+            }
+        }
+        chars.next();
+    }
+    (expanded, substituted)
+}
+
+/// Returns the extra arguments for browsers launched directly, as given via $WEBBROWSER_ARGS
+fn webbrowser_args(runner: &impl Runner) -> Vec<String> {
+    runner
+        .var("WEBBROWSER_ARGS")
+        .map(|args| split_args(&args))
+        .unwrap_or_default()
+}
+
+/// Splits `args` into words like a POSIX shell would, minus the expansions: a word ends at
+/// unquoted whitespace, quotes group words together, and a backslash escapes the next character
+/// (except within single quotes)
+fn split_args(args: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    let mut chars = args.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (None, '\'') | (None, '"') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (Some('\''), c) => word.get_or_insert_with(String::new).push(c),
+            (_, '\\') => {
+                if let Some(escaped) = chars.next() {
+                    word.get_or_insert_with(String::new).push(escaped);
+                }
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (_, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    words
+}
+
+/// Sets up the environment of a command which (directly or not) launches the browser
+fn apply_env(cmd: &mut Command, options: &BrowserOptions) {
+    if let Some(ref locale) = options.locale {
+        cmd.env("LANG", locale).env("LC_ALL", locale);
+    }
+}
+
+/// Turns `cmd` into the command which should be run to launch a graphical browser, which means
+/// prefixing the user's wrapper if `cmd` runs the browser directly, and running it all in its own
+/// systemd scope if asked to (and it's possible)
+fn launch_command(
+    runner: &impl Runner,
+    mut cmd: Command,
+    options: &BrowserOptions,
+    direct: bool,
+) -> Command {
+    apply_env(&mut cmd, options);
+    // a missing program must still fail as not found, so that the next one gets tried
+    if !runner.which(&cmd.get_program().to_string_lossy()) {
+        return cmd;
+    }
+    let wrapper: &[String] = match options.wrapper {
+        Some(ref wrapper) if direct => wrapper,
+        _ => &[],
+    };
+    let scoped = options.systemd_scope && runner.which("systemd-run");
+    let prefix: Vec<&str> = if scoped {
+        ["systemd-run", "--user", "--scope", "--quiet", "--"]
+            .iter()
+            .copied()
+            .chain(wrapper.iter().map(String::as_str))
+            .collect()
+    } else {
+        wrapper.iter().map(String::as_str).collect()
+    };
+    if prefix.is_empty() {
+        return cmd;
+    }
+    let mut launched = Command::new(prefix[0]);
+    launched
+        .args(&prefix[1..])
+        .arg(cmd.get_program())
+        .args(cmd.get_args());
+    for (key, value) in cmd.get_envs() {
+        match value {
+            Some(value) => launched.env(key, value),
+            None => launched.env_remove(key),
+        };
+    }
+    launched
+}
+
+/// Builds the command for a single $BROWSER entry, with `extra_args` following the entry's own
+/// arguments, right before the url (or the argument the entry places it in via %s). That's where
+/// flags like Firefox's `-new-tab` have to go, as they take the url as their value. The url is
+/// appended as an argument unless the entry already places it, or it's going to be passed via
+/// stdin instead.
+fn browser_env_command(
+    browser: &str,
+    url: &str,
+    extra_args: &[String],
+    url_via_stdin: bool,
+) -> Command {
+    let mut words = browser.split_whitespace();
+    let mut cmd = Command::new(words.next().unwrap_or_default());
+    let mut has_url = false;
+    for word in words {
+        let (arg, substituted) = expand_browser_escapes(word, url);
+        if substituted && !has_url {
+            cmd.args(extra_args);
+            has_url = true;
+        }
+        cmd.arg(arg);
+    }
+    if !has_url {
+        cmd.args(extra_args);
+        if !url_via_stdin {
+            // append the url as an argument only if it was not already set via %s
+            cmd.arg(url);
+        }
+    }
+    cmd
+}
+
+fn open_on_unix_using_browser_env(
+    runner: &impl Runner,
+    url: &str,
+    options: &BrowserOptions,
+    attempts: &mut Attempts,
+) -> Result<OpenStatus> {
+    if runner.var("BROWSER").is_none() {
+        return Err(Error::new(ErrorKind::NotFound, "BROWSER env not set"));
+    }
+    let mut unsupported = None;
+    for browser in browser_env_entries(runner) {
+        if attempts.stopped {
+            break;
+        }
+        let program = browser.split_whitespace().next().unwrap_or_default();
+        // the likes of xdg-open hand the url off, rather than being the browser themselves
+        let is_browser = Family::from_command(program).is_some();
+        let extra_args = match browser_args(
+            program,
+            options,
+            || runner.is_running(program),
+            |key| runner.var(key),
+        ) {
+            Ok(args) if is_browser => webbrowser_args(runner)
+                .into_iter()
+                .chain(args)
+                .collect::<Vec<_>>(),
+            Ok(args) => args,
+            Err(e) => {
+                // this entry can't honour the options, but a later one might
+                unsupported = Some(e);
+                continue;
+            }
+        };
+        let cmd = browser_env_command(&browser, url, &extra_args, options.url_via_stdin);
+        let mut cmd = launch_command(runner, cmd, options, is_browser);
+        let result = if options.url_via_stdin {
+            runner
+                .status_with_stdin(&mut cmd, url)
+                .map(OpenStatus::from)
+        } else {
+            run_opener(runner, &mut cmd, program, options)
+        };
+        if let Some(status) = attempts.ran(program, result) {
+            return Ok(status);
+        }
+    }
+    Err(unsupported
+        .unwrap_or_else(|| Error::new(ErrorKind::NotFound, "No valid command in $BROWSER")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::fs;
+    use std::sync::{Arc, Mutex};
+
+    /// A [Runner] simulating a system with a fixed environment and set of installed programs,
+    /// which records every command line it's asked to run
+    #[derive(Default)]
+    struct MockRunner {
+        env: HashMap<String, String>,
+        installed: Vec<String>,
+        outputs: HashMap<String, String>,
+        running: Vec<String>,
+        paths: Vec<String>,
+        failures: HashMap<String, ErrorKind>,
+        exit_codes: HashMap<String, i32>,
+        delay: Duration,
+        stdin: RefCell<Vec<String>>,
+        calls: RefCell<Vec<Vec<String>>>,
+    }
+
+    impl MockRunner {
+        fn new(installed: &[&str]) -> Self {
+            MockRunner {
+                installed: installed.iter().map(|s| s.to_string()).collect(),
+                ..Default::default()
+            }
+        }
+
+        fn with_env(mut self, key: &str, value: &str) -> Self {
+            self.env.insert(key.to_string(), value.to_string());
+            self
+        }
+
+        fn with_output(mut self, program: &str, stdout: &str) -> Self {
+            self.outputs.insert(program.to_string(), stdout.to_string());
+            self
+        }
+
+        fn with_failure(mut self, program: &str, kind: ErrorKind) -> Self {
+            self.failures.insert(program.to_string(), kind);
+            self
+        }
+
+        fn with_exit_code(mut self, program: &str, code: i32) -> Self {
+            self.exit_codes.insert(program.to_string(), code);
+            self
+        }
+
+        fn with_delay(mut self, delay: Duration) -> Self {
+            self.delay = delay;
+            self
+        }
+
+        fn with_running(mut self, program: &str) -> Self {
+            self.running.push(program.to_string());
+            self
+        }
+
+        fn with_path(mut self, path: &str) -> Self {
+            self.paths.push(path.to_string());
+            self
+        }
+
+        fn record(&self, cmd: &Command) -> Result<()> {
+            let mut argv = vec![cmd.get_program().to_string_lossy().into_owned()];
+            argv.extend(cmd.get_args().map(|arg| arg.to_string_lossy().into_owned()));
+            thread::sleep(self.delay);
+            let found = self.installed.contains(&argv[0]);
+            let failure = self.failures.get(&argv[0]).cloned();
+            self.calls.borrow_mut().push(argv);
+            if let Some(kind) = failure {
+                Err(Error::new(kind, "failed"))
+            } else if found {
+                Ok(())
+            } else {
+                Err(Error::new(ErrorKind::NotFound, "not installed"))
+            }
+        }
+
+        fn calls(&self) -> Vec<Vec<String>> {
+            self.calls.borrow().clone()
+        }
+    }
+
+    impl Runner for MockRunner {
+        fn var(&self, key: &str) -> Option<String> {
+            self.env.get(key).cloned()
+        }
+
+        fn which(&self, program: &str) -> bool {
+            self.installed.iter().any(|p| p == program)
+        }
+
+        fn status(&self, cmd: &mut Command) -> Result<ExitStatus> {
+            self.record(cmd)?;
+            let program = cmd.get_program().to_string_lossy();
+            let code = self.exit_codes.get(program.as_ref()).cloned().unwrap_or(0);
+            // wait(2) puts the exit code in the second byte
+            Ok(ExitStatus::from_raw(code << 8))
+        }
+
+        fn status_with_stdin(&self, cmd: &mut Command, input: &str) -> Result<ExitStatus> {
+            self.record(cmd)?;
+            self.stdin.borrow_mut().push(input.to_string());
+            Ok(ExitStatus::from_raw(0))
+        }
+
+        fn output(&self, cmd: &mut Command) -> Result<Output> {
+            self.record(cmd)?;
+            let program = cmd.get_program().to_string_lossy();
+            Ok(Output {
+                status: ExitStatus::from_raw(0),
+                stdout: self
+                    .outputs
+                    .get(program.as_ref())
+                    .cloned()
+                    .unwrap_or_default()
+                    .into_bytes(),
+                stderr: vec![],
+            })
+        }
+
+        fn spawn(&self, cmd: &mut Command) -> Result<Option<Child>> {
+            self.record(cmd).map(|_| None)
+        }
+
+        fn is_running(&self, program: &str) -> bool {
+            self.running.iter().any(|p| p == program)
+        }
+
+        fn exists(&self, path: &str) -> bool {
+            self.paths.iter().any(|p| p == path)
+        }
+    }
+
+    /// A [Runner] which really spawns processes, but with a fake environment
+    struct EnvRunner(HashMap<String, String>);
+
+    impl Runner for EnvRunner {
+        fn var(&self, key: &str) -> Option<String> {
+            self.0.get(key).cloned()
+        }
+
+        fn which(&self, program: &str) -> bool {
+            OsRunner.which(program)
+        }
+
+        fn status(&self, cmd: &mut Command) -> Result<ExitStatus> {
+            OsRunner.status(cmd)
+        }
+
+        fn status_with_stdin(&self, cmd: &mut Command, input: &str) -> Result<ExitStatus> {
+            OsRunner.status_with_stdin(cmd, input)
+        }
+
+        fn output(&self, cmd: &mut Command) -> Result<Output> {
+            OsRunner.output(cmd)
+        }
+
+        fn spawn(&self, cmd: &mut Command) -> Result<Option<Child>> {
+            OsRunner.spawn(cmd)
+        }
+
+        fn is_running(&self, _program: &str) -> bool {
+            false
+        }
+
+        fn exists(&self, _path: &str) -> bool {
+            false
+        }
+    }
+
+    /// A [Runner] which resolves programs to fake ones in a directory, and really spawns them
+    struct FakeBrowserRunner(std::path::PathBuf);
+
+    impl Runner for FakeBrowserRunner {
+        fn var(&self, _key: &str) -> Option<String> {
+            None
+        }
+
+        fn which(&self, program: &str) -> bool {
+            self.0.join(program).exists()
+        }
+
+        fn status(&self, cmd: &mut Command) -> Result<ExitStatus> {
+            OsRunner.status(&mut self.resolve(cmd))
+        }
+
+        fn status_with_stdin(&self, cmd: &mut Command, input: &str) -> Result<ExitStatus> {
+            OsRunner.status_with_stdin(&mut self.resolve(cmd), input)
+        }
+
+        fn output(&self, cmd: &mut Command) -> Result<Output> {
+            OsRunner.output(&mut self.resolve(cmd))
+        }
+
+        fn spawn(&self, cmd: &mut Command) -> Result<Option<Child>> {
+            OsRunner.spawn(&mut self.resolve(cmd))
+        }
+
+        fn is_running(&self, _program: &str) -> bool {
+            false
+        }
+
+        fn exists(&self, _path: &str) -> bool {
+            false
+        }
+    }
+
+    impl FakeBrowserRunner {
+        fn resolve(&self, cmd: &Command) -> Command {
+            let mut resolved = Command::new(self.0.join(cmd.get_program()));
+            resolved.args(cmd.get_args());
+            for (key, value) in cmd.get_envs() {
+                match value {
+                    Some(value) => resolved.env(key, value),
+                    None => resolved.env_remove(key),
+                };
+            }
+            resolved
+        }
+    }
+
+    /// Creates an executable shell script in a fresh temp dir, returning the dir and script path
+    fn fake_opener(name: &str, body: &str) -> (std::path::PathBuf, String) {
+        let dir = std::env::temp_dir().join(format!("webbrowser-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let script = dir.join(name);
+        fs::write(&script, format!("#!/bin/sh\n{}\n", body)).unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        let script = script.to_str().unwrap().to_string();
+        (dir, script)
+    }
+
+    const URL: &str = "http://example.com";
+
+    fn open(runner: &impl Runner, browser: Browser) -> Result<OpenStatus> {
+        open_with_runner(runner, browser, URL, &BrowserOptions::default())
+    }
+
+    #[test]
+    fn test_terminal_uses_lynx() {
+        let runner = MockRunner::new(&["lynx", "w3m"]);
+        assert!(open(&runner, Browser::Terminal).is_ok());
+        assert_eq!(runner.calls(), vec![vec!["lynx", URL]]);
+    }
+
+    #[test]
+    fn test_terminal_falls_back_to_w3m() {
+        let runner = MockRunner::new(&["w3m", "xdg-open"]);
+        assert!(open(&runner, Browser::Terminal).is_ok());
+        assert_eq!(runner.calls().last().unwrap(), &vec!["w3m", URL]);
+    }
+
+    #[test]
+    fn test_terminal_prefers_browser_env() {
+        let runner = MockRunner::new(&["lynx", "/usr/bin/w3m"])
+            .with_env("BROWSER", "firefox:/usr/bin/w3m -v");
+        assert!(open(&runner, Browser::Terminal).is_ok());
+        assert_eq!(runner.calls(), vec![vec!["/usr/bin/w3m", "-v", URL]]);
+    }
+
+    #[test]
+    fn test_terminal_not_found() {
+        let runner = MockRunner::new(&["xdg-open"]);
+        let err = open(&runner, Browser::Terminal).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert_eq!(runner.calls().len(), TEXT_BROWSERS.len());
+    }
+
+    #[test]
+    fn test_xdg_open_trusted_with_handler() {
+        let runner = MockRunner::new(&["xdg-open", "xdg-mime", "gvfs-open"])
+            .with_output("xdg-mime", "firefox.desktop\n");
+        assert!(open(&runner, Browser::Default).is_ok());
+        assert_eq!(runner.calls().last().unwrap(), &vec!["xdg-open", URL]);
+    }
+
+    #[test]
+    fn test_xdg_open_skipped_without_handler() {
+        // xdg-open would exit 0 here, but xdg-mime says nothing handles http
+        let runner =
+            MockRunner::new(&["xdg-open", "xdg-mime", "gvfs-open"]).with_output("xdg-mime", "\n");
+        assert!(open(&runner, Browser::Default).is_ok());
+        let calls = runner.calls();
+        assert!(calls.iter().all(|argv| argv[0] != "xdg-open"));
+        assert_eq!(calls.last().unwrap(), &vec!["gvfs-open", URL]);
+    }
+
+    #[test]
+    fn test_xdg_mime_not_asked_without_scheme() {
+        // a drive letter isn't a scheme, and there's none at all in a bare path
+        let runner = MockRunner::new(&["xdg-mime"]).with_output("xdg-mime", "\n");
+        assert!(!xdg_has_no_handler(&runner, "C:\\Users\\me\\index.html"));
+        assert!(!xdg_has_no_handler(&runner, "index.html"));
+        assert!(runner.calls().is_empty());
+        assert!(xdg_has_no_handler(&runner, "HTTP://example.com"));
+        assert_eq!(
+            runner.calls(),
+            vec![vec![
+                "xdg-mime",
+                "query",
+                "default",
+                "x-scheme-handler/http"
+            ]]
+        );
+    }
+
+    #[test]
+    fn test_xdg_open_trusted_without_xdg_mime() {
+        let runner = MockRunner::new(&["xdg-open", "gvfs-open"]);
+        assert!(open(&runner, Browser::Default).is_ok());
+        assert_eq!(runner.calls().last().unwrap(), &vec!["xdg-open", URL]);
+    }
+
+    #[test]
+    fn test_url_via_stdin() {
+        let runner = MockRunner::new(&["opener"]).with_env("BROWSER", "opener --read");
+        let mut options = BrowserOptions::new();
+        options.url_via_stdin(true);
+        assert!(open_with_runner(&runner, Browser::Default, URL, &options).is_ok());
+        assert_eq!(runner.calls(), vec![vec!["opener", "--read"]]);
+        assert_eq!(runner.stdin.borrow().clone(), vec![URL]);
+    }
+
+    #[test]
+    fn test_url_via_stdin_reaches_opener() {
+        let (dir, script) = fake_opener("stdin-opener", "cat > \"$(dirname \"$0\")/received\"");
+        let runner = EnvRunner(vec![("BROWSER".to_string(), script)].into_iter().collect());
+        let mut options = BrowserOptions::new();
+        options.url_via_stdin(true);
+        assert!(open_with_runner(&runner, Browser::Default, URL, &options).is_ok());
+        assert_eq!(fs::read_to_string(dir.join("received")).unwrap(), URL);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_handlr_only() {
+        let runner = MockRunner::new(&["handlr"]);
+        assert!(open(&runner, Browser::Default).is_ok());
+        assert_eq!(runner.calls().last().unwrap(), &vec!["handlr", "open", URL]);
+    }
+
+    #[test]
+    fn test_mimeo_only() {
+        let runner = MockRunner::new(&["mimeo", "gvfs-open"]);
+        assert!(open(&runner, Browser::Default).is_ok());
+        assert_eq!(runner.calls().last().unwrap(), &vec!["mimeo", URL]);
+    }
+
+    #[test]
+    fn test_chrome_falls_back_to_chromium() {
+        let runner = MockRunner::new(&["chromium"]);
+        assert!(open(&runner, Browser::Chrome).is_ok());
+        assert_eq!(runner.calls().last().unwrap(), &vec!["chromium", URL]);
+    }
+
+    #[test]
+    fn test_named_browser_not_found() {
+        let runner = MockRunner::new(&["xdg-open"]);
+        let err = open(&runner, Browser::Firefox).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_proxy_in_chromium_argv() {
+        let runner = MockRunner::new(&["google-chrome"]);
+        let mut options = BrowserOptions::new();
+        options.proxy("proxy.local:8080");
+        assert!(open_with_runner(&runner, Browser::Chrome, URL, &options).is_ok());
+        assert_eq!(
+            runner.calls(),
+            vec![vec![
+                "google-chrome",
+                "--proxy-server=proxy.local:8080",
+                URL
+            ]]
+        );
+    }
+
+    #[test]
+    fn test_proxy_ignored_for_firefox() {
+        let runner = MockRunner::new(&["firefox"]);
+        let mut options = BrowserOptions::new();
+        options.proxy("proxy.local:8080");
+        assert!(open_with_runner(&runner, Browser::Firefox, URL, &options).is_ok());
+        assert_eq!(runner.calls(), vec![vec!["firefox", URL]]);
+    }
+
+    fn incognito() -> BrowserOptions {
+        let mut options = BrowserOptions::new();
+        options.incognito(true);
+        options
+    }
+
+    #[test]
+    fn test_incognito_named_browser() {
+        let runner = MockRunner::new(&["firefox"]);
+        assert!(open_with_runner(&runner, Browser::Firefox, URL, &incognito()).is_ok());
+        assert_eq!(
+            runner.calls(),
+            vec![vec!["firefox", "-private-window", URL]]
+        );
+    }
+
+    #[test]
+    fn test_incognito_via_browser_env() {
+        let runner = MockRunner::new(&["/opt/brave/brave"])
+            .with_env("BROWSER", "/opt/brave/brave --new-window");
+        assert!(open_with_runner(&runner, Browser::Default, URL, &incognito()).is_ok());
+        assert_eq!(
+            runner.calls(),
+            vec![vec!["/opt/brave/brave", "--new-window", "--incognito", URL]]
+        );
+    }
+
+    #[test]
+    fn test_incognito_after_browser_env_args() {
+        // Firefox takes the url as the value of -private-window, so nothing can come in between
+        let runner = MockRunner::new(&["firefox"]).with_env("BROWSER", "firefox -P work");
+        assert!(open_with_runner(&runner, Browser::Default, URL, &incognito()).is_ok());
+        assert_eq!(
+            runner.calls(),
+            vec![vec!["firefox", "-P", "work", "-private-window", URL]]
+        );
+
+        let runner = MockRunner::new(&["firefox"]).with_env("BROWSER", "firefox %s -P work");
+        assert!(open_with_runner(&runner, Browser::Default, URL, &incognito()).is_ok());
+        assert_eq!(
+            runner.calls(),
+            vec![vec!["firefox", "-private-window", URL, "-P", "work"]]
+        );
+    }
+
+    #[test]
+    fn test_incognito_unsupported_default() {
+        let runner = MockRunner::new(&["xdg-open", "mybrowser"]).with_env("BROWSER", "mybrowser");
+        let err = open_with_runner(&runner, Browser::Default, URL, &incognito()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Unsupported);
+        assert!(err.to_string().contains("mybrowser"));
+        assert!(runner.calls().is_empty());
+
+        let runner = MockRunner::new(&["xdg-open"]);
+        let err = open_with_runner(&runner, Browser::Default, URL, &incognito()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Unsupported);
+        assert!(runner.calls().is_empty());
+    }
+
+    #[test]
+    fn test_managed_session_close() {
+        let (dir, _) = fake_opener("firefox", "exec sleep 30");
+        let runner = FakeBrowserRunner(dir.clone());
+        let options = BrowserOptions::default();
+        let child = spawn_with_runner(&runner, Browser::Firefox, URL, &options).unwrap();
+        let mut session = crate::BrowserSession { child };
+        assert!(session.is_managed());
+        assert!(session.close().is_ok());
+        let status = session.wait().unwrap();
+        assert!(!status.success());
+        assert_eq!(status.code(), None); // terminated by signal
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_launch_on_blank_page() {
+        let (dir, _) = fake_opener("opera", "echo \"$@\" > \"$(dirname \"$0\")/args\"");
+        let runner = FakeBrowserRunner(dir.clone());
+        let options = BrowserOptions::default();
+        let child = spawn_with_runner(&runner, Browser::Opera, crate::BLANK_PAGE, &options)
+            .unwrap()
+            .expect("opera is launched directly");
+        crate::BrowserSession { child: Some(child) }.wait().unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("args")).unwrap().trim(),
+            "about:blank"
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_locale_env() {
+        let (dir, _) = fake_opener(
+            "firefox",
+            "echo \"$LANG $LC_ALL\" > \"$(dirname \"$0\")/env\"",
+        );
+        let runner = FakeBrowserRunner(dir.clone());
+        let mut options = BrowserOptions::new();
+        options.locale("de_DE.UTF-8");
+        let child = spawn_with_runner(&runner, Browser::Firefox, URL, &options).unwrap();
+        child.unwrap().wait().unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("env")).unwrap().trim(),
+            "de_DE.UTF-8 de_DE.UTF-8"
+        );
+        assert_ne!(std::env::var("LC_ALL").ok().as_deref(), Some("de_DE.UTF-8"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_unmanaged_session() {
+        let runner = MockRunner::new(&["xdg-open"]);
+        let options = BrowserOptions::default();
+        let child = spawn_with_runner(&runner, Browser::Default, URL, &options).unwrap();
+        let mut session = crate::BrowserSession { child };
+        assert!(!session.is_managed());
+        assert_eq!(session.close().unwrap_err().kind(), ErrorKind::Unsupported);
+    }
+
+    #[test]
+    fn test_dry_run_records_chain() {
+        let runner = MockRunner::new(&["gnome-open", "xdg-mime"]).with_env("BROWSER", "mybrowser");
+        let tried = Arc::new(Mutex::new(Vec::new()));
+        let sink = tried.clone();
+        let mut options = BrowserOptions::new();
+        options.dry_run(true).dry_run_sink(move |program, args| {
+            let mut argv = vec![program.to_string()];
+            argv.extend_from_slice(args);
+            sink.lock().unwrap().push(argv);
+        });
+        let dry_run = DryRunRunner::new(&runner, &options);
+        let status = open_with_runner(&dry_run, Browser::Default, URL, &options).unwrap();
+        assert!(status.success());
+        assert_eq!(
+            tried.lock().unwrap().clone(),
+            vec![
+                vec!["mybrowser", URL],
+                vec!["xdg-open", URL],
+                vec!["handlr", "open", URL],
+                vec!["mimeo", URL],
+                vec!["gvfs-open", URL],
+                vec!["gnome-open", URL],
+            ]
+        );
+        // nothing was run, nor probed
+        assert!(runner.calls().is_empty());
+    }
+
+    #[test]
+    fn test_dry_run_nothing_installed() {
+        let runner = MockRunner::new(&[]);
+        let mut options = BrowserOptions::new();
+        options.dry_run(true).dry_run_sink(|_, _| {});
+        let dry_run = DryRunRunner::new(&runner, &options);
+        let err = open_with_runner(&dry_run, Browser::Default, URL, &options).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_firefox_new_tab_when_running() {
+        let runner = MockRunner::new(&["firefox"]).with_running("firefox");
+        assert!(open(&runner, Browser::Firefox).is_ok());
+        assert_eq!(runner.calls()[0], vec!["firefox", "-new-tab", URL]);
+
+        // -new-tab takes the url as its value, so it goes after a $BROWSER entry's own args
+        let runner = MockRunner::new(&["firefox"])
+            .with_running("firefox")
+            .with_env("BROWSER", "firefox --profile /p");
+        assert!(open(&runner, Browser::Default).is_ok());
+        assert_eq!(
+            runner.calls()[0],
+            vec!["firefox", "--profile", "/p", "-new-tab", URL]
+        );
+    }
+
+    #[test]
+    fn test_firefox_fresh_when_not_running() {
+        let runner = MockRunner::new(&["firefox"]);
+        assert!(open(&runner, Browser::Firefox).is_ok());
+        assert_eq!(runner.calls()[0], vec!["firefox", URL]);
+    }
+
+    #[test]
+    fn test_is_running_self() {
+        // the test binary itself is as good a running process as any
+        let exe = std::env::current_exe().unwrap();
+        assert!(OsRunner.is_running(exe.file_name().unwrap().to_str().unwrap()));
+        assert!(!OsRunner.is_running("surely-no-such-browser"));
+    }
+
+    #[test]
+    fn test_report_walks_whole_chain() {
+        let runner = MockRunner::new(&["mybrowser", "xdg-open", "gnome-open"])
+            .with_env("BROWSER", "mybrowser:otherbrowser");
+        let report = report_with_runner(&runner, Browser::Default, URL, &BrowserOptions::new());
+        let tried: Vec<_> = report.iter().map(|(cmd, _)| cmd.as_str()).collect();
+        let mut expected = vec![
+            "mybrowser http://example.com",
+            "otherbrowser http://example.com",
+            "xdg-open http://example.com",
+            "handlr open http://example.com",
+            "mimeo http://example.com",
+            "gvfs-open http://example.com",
+            "gnome-open http://example.com",
+            "open http://example.com",
+            "kioclient exec http://example.com",
+            "x-www-browser http://example.com",
+        ];
+        if !BARE_OPEN_IS_OPENER {
+            expected.retain(|cmd| !cmd.starts_with("open "));
+        }
+        assert_eq!(tried, expected);
+        let succeeded: Vec<_> = report
+            .iter()
+            .filter(|(_, result)| result.is_ok())
+            .map(|(cmd, _)| cmd.as_str())
+            .collect();
+        assert_eq!(
+            succeeded,
+            vec![
+                "mybrowser http://example.com",
+                "xdg-open http://example.com",
+                "gnome-open http://example.com",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_systemd_scope() {
+        let runner = MockRunner::new(&["systemd-run", "firefox"]);
+        let mut options = BrowserOptions::new();
+        options.systemd_scope(true);
+        assert!(open_with_runner(&runner, Browser::Firefox, URL, &options).is_ok());
+        assert_eq!(
+            runner.calls(),
+            vec![vec![
+                "systemd-run",
+                "--user",
+                "--scope",
+                "--quiet",
+                "--",
+                "firefox",
+                URL
+            ]]
+        );
+    }
+
+    #[test]
+    fn test_webbrowser_args() {
+        let runner = MockRunner::new(&["chromium", "xdg-open"])
+            .with_env("WEBBROWSER_ARGS", "--no-sandbox --disable-gpu");
+        assert!(open(&runner, Browser::Chrome).is_ok());
+        assert_eq!(
+            runner.calls().last().unwrap(),
+            &vec!["chromium", "--no-sandbox", "--disable-gpu", URL]
+        );
+
+        // handing off to xdg-open doesn't get them
+        assert!(open(&runner, Browser::Default).is_ok());
+        assert_eq!(runner.calls().last().unwrap(), &vec!["xdg-open", URL]);
+    }
+
+    #[test]
+    fn test_webbrowser_args_with_browser_env() {
+        let runner = MockRunner::new(&["chromium"])
+            .with_env("BROWSER", "chromium --flag")
+            .with_env("WEBBROWSER_ARGS", "--no-sandbox");
+        assert!(open(&runner, Browser::Default).is_ok());
+        assert_eq!(
+            runner.calls()[0],
+            vec!["chromium", "--flag", "--no-sandbox", URL]
+        );
+
+        // handing off to xdg-open via $BROWSER doesn't get them either
+        let runner = MockRunner::new(&["xdg-open"])
+            .with_env("BROWSER", "xdg-open")
+            .with_env("WEBBROWSER_ARGS", "--no-sandbox");
+        assert!(open(&runner, Browser::Default).is_ok());
+        assert_eq!(runner.calls().last().unwrap(), &vec!["xdg-open", URL]);
+    }
+
+    #[test]
+    fn test_split_args() {
+        assert_eq!(
+            split_args("  --no-sandbox\t--disable-gpu "),
+            vec!["--no-sandbox", "--disable-gpu"]
+        );
+        assert_eq!(
+            split_args(r#"--user-data-dir="/tmp/my profile" --title='it'"'"'s' a\ b"#),
+            vec!["--user-data-dir=/tmp/my profile", "--title=it's", "a b"]
+        );
+        assert_eq!(split_args(r#"'' "a\"b""#), vec!["", "a\"b"]);
+        assert!(split_args("").is_empty());
+    }
+
+    #[test]
+    fn test_inherit_proxy_env() {
+        let runner = MockRunner::new(&["chromium"])
+            .with_env("https_proxy", "http://proxy.example.com:3128")
+            .with_env("no_proxy", "localhost");
+        let mut options = BrowserOptions::new();
+        options.inherit_proxy_env(true);
+        assert!(open_with_runner(&runner, Browser::Chrome, URL, &options).is_ok());
+        assert_eq!(
+            runner.calls().last().unwrap(),
+            &vec![
+                "chromium",
+                "--proxy-server=http://proxy.example.com:3128",
+                "--proxy-bypass-list=localhost",
+                URL
+            ]
+        );
+    }
+
+    #[test]
+    fn test_wrapper() {
+        let runner = MockRunner::new(&["firejail", "firefox", "xdg-open"]);
+        let mut options = BrowserOptions::new();
+        options.wrapper(vec!["firejail".to_string()]);
+        assert!(open_with_runner(&runner, Browser::Firefox, URL, &options).is_ok());
+        assert_eq!(runner.calls(), vec![vec!["firejail", "firefox", URL]]);
+
+        // handing off to xdg-open doesn't get wrapped
+        assert!(open_with_runner(&runner, Browser::Default, URL, &options).is_ok());
+        assert_eq!(runner.calls().last().unwrap(), &vec!["xdg-open", URL]);
+
+        // not even via $BROWSER, unlike a browser set there
+        let runner = MockRunner::new(&["firejail", "firefox", "xdg-open"])
+            .with_env("BROWSER", "xdg-open:firefox");
+        assert!(open_with_runner(&runner, Browser::Default, URL, &options).is_ok());
+        assert_eq!(runner.calls(), vec![vec!["xdg-open", URL]]);
+        let runner = MockRunner::new(&["firejail", "firefox"]).with_env("BROWSER", "firefox");
+        assert!(open_with_runner(&runner, Browser::Default, URL, &options).is_ok());
+        assert_eq!(runner.calls(), vec![vec!["firejail", "firefox", URL]]);
+    }
+
+    #[test]
+    fn test_wrapper_in_systemd_scope() {
+        let runner = MockRunner::new(&["systemd-run", "firefox"]);
+        let mut options = BrowserOptions::new();
+        options
+            .systemd_scope(true)
+            .wrapper(vec!["sudo".into(), "-u".into(), "browseruser".into()]);
+        assert!(open_with_runner(&runner, Browser::Firefox, URL, &options).is_ok());
+        assert_eq!(
+            runner.calls()[0],
+            vec![
+                "systemd-run",
+                "--user",
+                "--scope",
+                "--quiet",
+                "--",
+                "sudo",
+                "-u",
+                "browseruser",
+                "firefox",
+                URL
+            ]
+        );
+    }
+
+    #[test]
+    fn test_systemd_scope_unavailable() {
+        let runner = MockRunner::new(&["xdg-open"]);
+        let mut options = BrowserOptions::new();
+        options.systemd_scope(true);
+        assert!(open_with_runner(&runner, Browser::Default, URL, &options).is_ok());
+        assert_eq!(runner.calls().last().unwrap(), &vec!["xdg-open", URL]);
+    }
+
+    #[test]
+    fn test_custom_scheme_skips_browser_env() {
+        let runner = MockRunner::new(&["firefox", "xdg-open"]).with_env("BROWSER", "firefox");
+        let url = "steam://run/440";
+        assert!(open_with_runner(&runner, Browser::Default, url, &incognito()).is_ok());
+        assert!(runner.calls().iter().all(|argv| argv[0] != "firefox"));
+        assert_eq!(runner.calls().last().unwrap(), &vec!["xdg-open", url]);
+    }
+
+    #[test]
+    fn test_raw_custom_scheme_uses_browser_env() {
+        let runner = MockRunner::new(&["firefox", "xdg-open"]).with_env("BROWSER", "firefox");
+        let url = "steam://run/440";
+        let mut options = BrowserOptions::new();
+        options.raw(true);
+        assert!(open_with_runner(&runner, Browser::Default, url, &options).is_ok());
+        assert_eq!(runner.calls().last().unwrap(), &vec!["firefox", url]);
+    }
+
+    #[test]
+    fn test_missing_browser_strict() {
+        let runner = MockRunner::new(&["xdg-open"]);
+        let options = BrowserOptions::new();
+        let err = crate::open_with_fallback(Browser::Chrome, &options, |browser| {
+            open_with_runner(&runner, browser, URL, &options)
+        })
+        .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert!(runner.calls().iter().all(|argv| argv[0] != "xdg-open"));
+    }
+
+    #[test]
+    fn test_missing_browser_fallback() {
+        let runner = MockRunner::new(&["xdg-open"]);
+        let mut options = BrowserOptions::new();
+        options.fallback_to_default(true);
+        let outcome = crate::open_with_fallback(Browser::Chrome, &options, |browser| {
+            open_with_runner(&runner, browser, URL, &options)
+        })
+        .unwrap();
+        assert!(outcome.fell_back());
+        assert_eq!(outcome.browser(), Browser::Default);
+        assert!(outcome.status().success());
+        assert_eq!(runner.calls().last().unwrap(), &vec!["xdg-open", URL]);
+    }
+
+    #[test]
+    fn test_deadline_stops_chain() {
+        let runner = MockRunner::new(&[]).with_delay(Duration::from_millis(100));
+        let deadline = Instant::now() + Duration::from_millis(150);
+        let deadline_runner = DeadlineRunner::new(&runner, deadline);
+        let err = open(&deadline_runner, Browser::Default).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TimedOut);
+        // xdg-mime gets asked about a handler, and then only xdg-open gets a go
+        assert_eq!(
+            runner
+                .calls()
+                .iter()
+                .map(|argv| argv[0].as_str())
+                .collect::<Vec<_>>(),
+            vec!["xdg-mime", "xdg-open"]
+        );
+    }
+
+    #[test]
+    fn test_deadline_kills_slow_opener() {
+        let (dir, _) = fake_opener("xdg-open", "exec sleep 30");
+        let runner = FakeBrowserRunner(dir.clone());
+        let start = Instant::now();
+        let deadline_runner = DeadlineRunner::new(&runner, start + Duration::from_millis(200));
+        let err = open(&deadline_runner, Browser::Default).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TimedOut);
+        assert!(start.elapsed() < Duration::from_secs(10));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_deadline_kills_slow_stdin_opener() {
+        let (dir, script) = fake_opener("stdin-opener", "read url; exec sleep 30");
+        let start = Instant::now();
+        let deadline_runner = DeadlineRunner::new(&OsRunner, start + Duration::from_millis(200));
+        let err = deadline_runner
+            .status_with_stdin(&mut Command::new(script), URL)
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TimedOut);
+        assert!(start.elapsed() < Duration::from_secs(10));
+        fs::remove_dir_all(dir).unwrap();
+
+        // one which is done in time still gets the url
+        let (dir, script) = fake_opener("stdin-reader", "exec cat > \"$0.url\"");
+        let deadline_runner =
+            DeadlineRunner::new(&OsRunner, Instant::now() + Duration::from_secs(10));
+        let status = deadline_runner
+            .status_with_stdin(&mut Command::new(&script), URL)
+            .unwrap();
+        assert!(status.success());
+        assert_eq!(fs::read_to_string(format!("{}.url", script)).unwrap(), URL);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_first_meaningful_error() {
+        let runner = MockRunner::new(&[])
+            .with_env("BROWSER", "mybrowser")
+            .with_failure("xdg-open", ErrorKind::PermissionDenied);
+        let err = open(&runner, Browser::Default).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+        assert!(err.to_string().starts_with("xdg-open: failed"));
+    }
+
+    #[test]
+    fn test_no_opener_summary() {
+        let runner = MockRunner::new(&[]).with_env("BROWSER", "mybrowser");
+        let err = open(&runner, Browser::Default).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert!(err.to_string().starts_with(
+            if BARE_OPEN_IS_OPENER {
+                "no opener found (tried mybrowser, xdg-open, handlr, mimeo, gvfs-open, gnome-open, open, kioclient, x-www-browser)"
+            } else {
+                "no opener found (tried mybrowser, xdg-open, handlr, mimeo, gvfs-open, gnome-open, kioclient, x-www-browser)"
+            }
+        ));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_no_bare_open_on_linux() {
+        let openers = system_openers(&MockRunner::new(&[]), &BrowserOptions::default());
+        assert!(openers.iter().all(|opener| opener.program != "open"));
+        assert!(openers.iter().any(|opener| opener.program == "xdg-open"));
+    }
+
+    #[test]
+    fn test_openers_tried_once() {
+        for desktop in &["KDE", "GNOME"] {
+            let runner = MockRunner::new(&[]).with_env("XDG_CURRENT_DESKTOP", desktop);
+            let _ = open(&runner, Browser::Default);
+            let kioclient = runner
+                .calls()
+                .iter()
+                .filter(|call| call[0] == "kioclient")
+                .count();
+            assert_eq!(kioclient, 1, "on {}", desktop);
+        }
+
+        let runner = MockRunner::new(&[]).with_env("BROWSER", "xdg-open");
+        let _ = open(&runner, Browser::Default);
+        let xdg_open = runner
+            .calls()
+            .iter()
+            .filter(|call| call[0] == "xdg-open")
+            .count();
+        assert_eq!(xdg_open, 1);
+    }
+
+    /// Creates a data dir in a fresh temp dir, with a desktop entry for `id` running `exec`
+    fn fake_desktop_entry(id: &str, exec: &str) -> std::path::PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("webbrowser-desktop-{}-{}", id, std::process::id()));
+        fs::create_dir_all(dir.join("applications")).unwrap();
+        fs::write(
+            dir.join("applications").join(format!("{}.desktop", id)),
+            format!(
+                "[Desktop Entry]\nType=Application\nExec={}\n\n[Desktop Action new-window]\nExec=other\n",
+                exec
+            ),
+        )
+        .unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_desktop_app_via_gtk_launch() {
+        let dir = fake_desktop_entry("mybrowser", "mybrowser %u");
+        let runner = MockRunner::new(&["gtk-launch", "mybrowser"])
+            .with_env("XDG_DATA_DIRS", dir.to_str().unwrap());
+        let options = BrowserOptions::default();
+        desktop_app_with_runner(&runner, "mybrowser.desktop", URL, &options).unwrap();
+        desktop_app_with_runner(&runner, "mybrowser", URL, &options).unwrap();
+        assert_eq!(
+            runner.calls(),
+            vec![
+                vec!["gtk-launch", "mybrowser.desktop", URL],
+                vec!["gtk-launch", "mybrowser.desktop", URL]
+            ]
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_desktop_app_via_exec() {
+        let dir = fake_desktop_entry("mybrowser2", "\"/opt/my browser/bin\" --new %U %i");
+        let runner = MockRunner::new(&["/opt/my browser/bin"])
+            .with_env("XDG_DATA_HOME", dir.to_str().unwrap())
+            .with_env("XDG_DATA_DIRS", "/nonexistent");
+        desktop_app_with_runner(&runner, "mybrowser2", URL, &BrowserOptions::default()).unwrap();
+        assert_eq!(
+            runner.calls(),
+            vec![vec!["/opt/my browser/bin", "--new", URL]]
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_desktop_app_not_found() {
+        let runner = MockRunner::new(&["gtk-launch"])
+            .with_env("XDG_DATA_HOME", "/nonexistent")
+            .with_env("XDG_DATA_DIRS", "/nonexistent");
+        let err = desktop_app_with_runner(&runner, "nothing", URL, &BrowserOptions::default())
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert!(runner.calls().is_empty());
+    }
+
+    #[test]
+    fn test_exec_args() {
+        assert_eq!(exec_args("firefox %u", URL), vec!["firefox", URL]);
+        assert_eq!(
+            exec_args("app --x=100%%", URL),
+            vec!["app", "--x=100%", URL]
+        );
+        assert_eq!(
+            desktop_exec("[Other]\nExec=no\n[Desktop Entry]\nExec = yes %u"),
+            Some("yes %u".to_string())
+        );
+    }
+
+    #[test]
+    fn test_failure_suggests_command() {
+        let err = open_with_runner(
+            &MockRunner::new(&[]),
+            Browser::Default,
+            "http://example.com/?a=1&b=$HOME",
+            &BrowserOptions::default(),
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert!(err
+            .to_string()
+            .ends_with("run: xdg-open 'http://example.com/?a=1&b=$HOME'"));
+    }
+
+    #[test]
+    fn test_wsl_prefers_host_browser() {
+        let runner =
+            MockRunner::new(&["wslview", "xdg-open"]).with_env("WSL_DISTRO_NAME", "Ubuntu");
+        assert!(open(&runner, Browser::Default).is_ok());
+        assert_eq!(runner.calls().last().unwrap(), &vec!["wslview", URL]);
+    }
+
+    #[test]
+    fn test_wsl_prefers_linux_browser() {
+        let runner =
+            MockRunner::new(&["wslview", "xdg-open"]).with_env("WSL_DISTRO_NAME", "Ubuntu");
+        let mut options = BrowserOptions::new();
+        options.prefer_host_browser(false);
+        assert!(open_with_runner(&runner, Browser::Default, URL, &options).is_ok());
+        assert_eq!(runner.calls().last().unwrap(), &vec!["xdg-open", URL]);
+    }
+
+    #[test]
+    fn test_wsl_powershell_fallback() {
+        let runner =
+            MockRunner::new(&["powershell.exe"]).with_env("WSL_INTEROP", "/run/WSL/1_interop");
+        let url = "http://example.com/?a=1&b='2";
+        assert!(
+            open_with_runner(&runner, Browser::Default, url, &BrowserOptions::default()).is_ok()
+        );
+        assert_eq!(
+            runner.calls().last().unwrap().last().unwrap(),
+            "'http://example.com/?a=1&b=''2'"
+        );
+    }
+
+    #[test]
+    fn test_crostini_prefers_garcon() {
+        let runner = MockRunner::new(&["garcon-url-handler", "xdg-open"]);
+        assert!(open(&runner, Browser::Default).is_ok());
+        let calls = runner.calls();
+        assert_eq!(calls.last().unwrap(), &vec!["garcon-url-handler", URL]);
+        assert!(calls.iter().all(|call| call[0] != "xdg-open"));
+
+        // the rest of the chain is still there if it doesn't work out
+        let runner = MockRunner::new(&["garcon-url-handler", "xdg-open"])
+            .with_exit_code("garcon-url-handler", 1);
+        assert!(open(&runner, Browser::Default).is_ok());
+        assert_eq!(runner.calls().last().unwrap(), &vec!["xdg-open", URL]);
+    }
+
+    #[test]
+    fn test_crostini_detected_by_milestone() {
+        let runner = MockRunner::new(&[GARCON_URL_HANDLER, "xdg-open"]).with_path(CROS_MILESTONE);
+        assert!(open(&runner, Browser::Default).is_ok());
+        assert_eq!(
+            runner.calls().last().unwrap(),
+            &vec![GARCON_URL_HANDLER, URL]
+        );
+
+        // without the milestone it's just another linux, even with the handler lying around
+        let runner = MockRunner::new(&[GARCON_URL_HANDLER, "xdg-open"]);
+        assert!(open(&runner, Browser::Default).is_ok());
+        assert!(runner
+            .calls()
+            .iter()
+            .all(|call| call[0] != GARCON_URL_HANDLER));
+    }
+
+    #[test]
+    fn test_resolved_opener_reused() {
+        let runner = MockRunner::new(&["firefox", "xdg-open"]).with_env("BROWSER", "firefox");
+        let mut options = BrowserOptions::new();
+        options.incognito(true);
+        let resolved = resolve_with_runner(&runner, Browser::Default, &options).unwrap();
+        // nothing is run (or probed) while resolving, nor once it's done
+        assert!(runner.calls().is_empty());
+        for url in &["http://a.example/", "http://b.example/?q=1"] {
+            open_resolved_with_runner(&runner, &resolved, url).unwrap();
+        }
+        assert_eq!(
+            runner.calls(),
+            vec![
+                vec!["firefox", "-private-window", "http://a.example/"],
+                vec!["firefox", "-private-window", "http://b.example/?q=1"],
+            ]
+        );
+
+        // incognito is up to $BROWSER
+        let runner = MockRunner::new(&["xdg-open"]);
+        let err = resolve_with_runner(&runner, Browser::Default, &options).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Unsupported);
+    }
+
+    #[test]
+    fn test_resolved_opener_quoting() {
+        let runner =
+            MockRunner::new(&["powershell.exe"]).with_env("WSL_INTEROP", "/run/WSL/1_interop");
+        let resolved =
+            resolve_with_runner(&runner, Browser::Default, &BrowserOptions::default()).unwrap();
+        open_resolved_with_runner(&runner, &resolved, "http://example.com/?b='2").unwrap();
+        assert_eq!(
+            runner.calls().last().unwrap().last().unwrap(),
+            "'http://example.com/?b=''2'"
+        );
+    }
+
+    #[test]
+    fn test_no_host_openers_outside_wsl() {
+        let runner = MockRunner::new(&["wslview"]);
+        assert!(open(&runner, Browser::Default).is_err());
+    }
+
+    #[test]
+    fn test_browser_env_escapes() {
+        assert_eq!(
+            expand_browser_escapes("--url=%s", URL),
+            (format!("--url={}", URL), true)
+        );
+        assert_eq!(
+            expand_browser_escapes("%%s", URL),
+            ("%s".to_string(), false)
+        );
+        assert_eq!(
+            expand_browser_escapes("localhost%c8080", URL),
+            ("localhost:8080".to_string(), false)
+        );
+        assert_eq!(
+            expand_browser_escapes("100%%-%x%", URL),
+            ("100%-%x%".to_string(), false)
+        );
+        // the url is never expanded again, even if it looks like it has escapes in it
+        assert_eq!(
+            expand_browser_escapes("%s", "http://example.com/%cafe%s"),
+            ("http://example.com/%cafe%s".to_string(), true)
+        );
+    }
+
+    #[test]
+    fn test_browser_env_escaped_placeholder_appends_url() {
+        let runner = MockRunner::new(&[]).with_env("BROWSER", "mybrowser --literal=%%s");
+        let _ = open(&runner, Browser::Default);
+        assert_eq!(runner.calls()[0], vec!["mybrowser", "--literal=%s", URL]);
+    }
+
+    #[test]
+    fn test_default_capabilities() {
+        let runner = MockRunner::new(&[]);
+        assert_eq!(
+            default_capabilities_with_runner(&runner),
+            BrowserCapabilities::default()
+        );
+        let runner = MockRunner::new(&[]).with_env("BROWSER", "lynx:/usr/bin/firefox-esr");
+        assert!(!default_capabilities_with_runner(&runner).app_mode);
+        assert!(default_capabilities_with_runner(&runner).incognito);
+    }
+
+    #[test]
+    fn test_browser_version() {
+        let runner = MockRunner::new(&["chromium"])
+            .with_output("chromium", "Chromium 118.0.5993.70 built on Debian 12.2\n");
+        assert_eq!(
+            version_with_runner(&runner, Browser::Chrome).unwrap(),
+            "118.0.5993.70"
+        );
+        assert_eq!(runner.calls(), vec![vec!["chromium", "--version"]]);
+
+        let runner = MockRunner::new(&["/opt/firefox/firefox"])
+            .with_env("BROWSER", "/opt/firefox/firefox")
+            .with_output("/opt/firefox/firefox", "Mozilla Firefox 118.0\n");
+        assert_eq!(
+            version_with_runner(&runner, Browser::Default).unwrap(),
+            "118.0"
+        );
+    }
+
+    #[test]
+    fn test_browser_version_not_installed() {
+        let runner = MockRunner::new(&[]);
+        let err = version_with_runner(&runner, Browser::Firefox).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert!(runner.calls().is_empty());
+    }
+
+    #[test]
+    fn test_is_available() {
+        assert!(!is_available_with_runner(
+            &MockRunner::new(&[]),
+            Browser::Default
+        ));
+        assert!(is_available_with_runner(
+            &MockRunner::new(&["mimeo"]),
+            Browser::Default
+        ));
+        let runner =
+            MockRunner::new(&["/opt/bin/mybrowser"]).with_env("BROWSER", "/opt/bin/mybrowser -x");
+        assert!(is_available_with_runner(&runner, Browser::Default));
+        assert!(!is_available_with_runner(&runner, Browser::Terminal));
+        assert!(is_available_with_runner(
+            &MockRunner::new(&["w3m"]),
+            Browser::Terminal
+        ));
+        assert!(is_available_with_runner(
+            &MockRunner::new(&["chromium"]),
+            Browser::Chrome
+        ));
+        assert!(!is_available_with_runner(
+            &MockRunner::new(&["chromium"]),
+            Browser::Firefox
+        ));
+        assert!(!is_available_with_runner(
+            &MockRunner::new(&["xdg-open"]),
+            Browser::Safari
+        ));
+    }
+
+    #[test]
+    fn test_unsupported_browser() {
+        let runner = MockRunner::new(&["xdg-open"]);
+        let err = open(&runner, Browser::InternetExplorer).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert_eq!(
+            err.to_string(),
+            "Internet Explorer is not available on this platform"
+        );
+        let inner = err
+            .get_ref()
+            .and_then(|e| e.downcast_ref::<crate::UnsupportedBrowserError>())
+            .unwrap();
+        assert_eq!(inner.browser(), Browser::InternetExplorer);
+        assert!(runner.calls().is_empty());
+
+        // more to the point than not being installed, but still something to fall back from
+        let mut options = BrowserOptions::new();
+        options.fallback_to_default(true);
+        let outcome = crate::open_with_fallback(Browser::Safari, &options, |browser| {
+            open_with_runner(&runner, browser, URL, &options)
+        })
+        .unwrap();
+        assert_eq!(outcome.browser(), Browser::Default);
+    }
+
+    #[test]
+    fn test_blocking_same_with_or_without_browser_env() {
+        let with_env = MockRunner::new(&["mybrowser", "xdg-open"]).with_env("BROWSER", "mybrowser");
+        let without_env = MockRunner::new(&["mybrowser", "xdg-open"]);
+        let mut options = BrowserOptions::new();
+        for blocking in &[true, false] {
+            options.blocking(*blocking);
+            let codes: Vec<_> = [&with_env, &without_env]
+                .iter()
+                .map(|runner| {
+                    open_with_runner(*runner, Browser::Default, URL, &options)
+                        .unwrap()
+                        .code()
+                })
+                .collect();
+            // the exit code is only known if the opener was waited for
+            let expected = if *blocking { Some(0) } else { None };
+            assert_eq!(codes, vec![expected, expected], "blocking: {}", blocking);
+        }
+    }
+
+    #[test]
+    fn test_blocking_fake_openers() {
+        let (browser_dir, browser) = fake_opener("fast-browser", "exit 3");
+        let (opener_dir, _) = fake_opener("mimeo", "exit 3");
+        let browser_env = EnvRunner(
+            vec![("BROWSER".to_string(), browser.clone())]
+                .into_iter()
+                .collect(),
+        );
+        let chain = FakeBrowserRunner(opener_dir.clone());
+        let mut options = BrowserOptions::new();
+        // so that nothing else gets tried after the fake ones fail
+        options.strict(true);
+        let status = open_with_runner(&browser_env, Browser::Default, URL, &options).unwrap();
+        assert_eq!(status.code(), Some(3));
+        let status = open_with_runner(&chain, Browser::Default, URL, &options).unwrap();
+        assert_eq!(status.code(), Some(3));
+
+        options.blocking(false);
+        let status = open_with_runner(&browser_env, Browser::Default, URL, &options).unwrap();
+        assert!(status.success() && status.code().is_none());
+        let status = open_with_runner(&chain, Browser::Default, URL, &options).unwrap();
+        assert!(status.success() && status.code().is_none());
+        fs::remove_dir_all(browser_dir).unwrap();
+        fs::remove_dir_all(opener_dir).unwrap();
+    }
+
+    #[test]
+    fn test_text_browser_always_inline() {
+        let runner = MockRunner::new(&["lynx"]).with_env("BROWSER", "lynx");
+        let mut options = BrowserOptions::new();
+        options.blocking(false);
+        let status = open_with_runner(&runner, Browser::Default, URL, &options).unwrap();
+        assert_eq!(status.code(), Some(0));
+    }
+
+    #[test]
+    fn test_template_command() {
+        let argv = |template| {
+            let (program, args) = crate::command_line(&template_command(template, URL).unwrap());
+            let mut argv = vec![program];
+            argv.extend(args);
+            argv
+        };
+        assert_eq!(
+            argv("myproxy open --url %s --flag"),
+            vec!["myproxy", "open", "--url", URL, "--flag"]
+        );
+        assert_eq!(argv("myproxy open"), vec!["myproxy", "open", URL]);
+        assert_eq!(
+            argv("'/opt/my proxy/bin' --title \"a b\" --url=%u"),
+            vec![
+                "/opt/my proxy/bin",
+                "--title",
+                "a b",
+                "--url=http://example.com"
+            ]
+        );
+        // escaped placeholders don't count
+        assert_eq!(argv("echo 100%%s"), vec!["echo", "100%s", URL]);
+        assert_eq!(
+            template_command("  ", URL).unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+    }
+
+    #[test]
+    fn test_open_with_template() {
+        let runner = MockRunner::new(&["myproxy"]);
+        let options = BrowserOptions::new();
+        template_with_runner(&runner, "myproxy --url %s", URL, &options).unwrap();
+        assert_eq!(runner.calls(), vec![vec!["myproxy", "--url", URL]]);
+        let err = template_with_runner(&runner, "missing %s", URL, &options).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_strict_and_lenient() {
+        let runner = || {
+            MockRunner::new(&["mybrowser", "xdg-open", "gnome-open"])
+                .with_env("BROWSER", "mybrowser")
+                .with_exit_code("mybrowser", 1)
+        };
+        let mut options = BrowserOptions::new();
+
+        // lenient moves on to the next one
+        let lenient = runner();
+        let status = open_with_runner(&lenient, Browser::Default, URL, &options).unwrap();
+        assert!(status.success());
+        let programs: Vec<_> = lenient
+            .calls()
+            .into_iter()
+            .map(|call| call[0].clone())
+            .filter(|program| program != "xdg-mime")
+            .collect();
+        assert_eq!(programs, vec!["mybrowser", "xdg-open"]);
+
+        // strict stops at the first one which is there
+        options.strict(true);
+        let strict = runner();
+        let status = open_with_runner(&strict, Browser::Default, URL, &options).unwrap();
+        assert_eq!(status.code(), Some(1));
+        assert_eq!(strict.calls(), vec![vec!["mybrowser", URL]]);
+
+        // and surfaces a launch error as is
+        let strict = runner().with_failure("mybrowser", ErrorKind::PermissionDenied);
+        let err = open_with_runner(&strict, Browser::Default, URL, &options).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+        assert!(err.to_string().starts_with("mybrowser: failed"));
+        assert_eq!(strict.calls().len(), 1);
+
+        // missing openers are skipped either way
+        let strict = MockRunner::new(&["gnome-open"]).with_env("BROWSER", "mybrowser");
+        assert!(open_with_runner(&strict, Browser::Default, URL, &options)
+            .unwrap()
+            .success());
+    }
+
+    #[test]
+    fn test_lenient_reports_first_failed_status() {
+        let runner = MockRunner::new(&["xdg-open", "gnome-open"])
+            .with_exit_code("xdg-open", 4)
+            .with_exit_code("gnome-open", 1);
+        let status = open(&runner, Browser::Default).unwrap();
+        assert_eq!(status.code(), Some(4));
+        assert!(runner.calls().iter().any(|call| call[0] == "gnome-open"));
+    }
+
+    #[test]
+    fn test_is_text_browser() {
+        assert!(is_text_browser("lynx"));
+        assert!(is_text_browser("/usr/local/bin/w3m"));
+        assert!(!is_text_browser("firefox"));
+    }
 }
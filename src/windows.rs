@@ -1,9 +1,12 @@
 extern crate widestring;
 extern crate winapi;
 
-use crate::{Browser, Error, ErrorKind, Result};
+use crate::{
+    encode_url_path, shell_quote_command, Browser, BrowserOptions, Error, ErrorKind, OpenStatus,
+    Result,
+};
 pub use std::os::windows::process::ExitStatusExt;
-use std::process::ExitStatus;
+use std::path::Path;
 use std::ptr;
 use widestring::U16CString;
 
@@ -11,16 +14,30 @@ use widestring::U16CString;
 /// https://docs.microsoft.com/en-us/windows/desktop/api/shellapi/nf-shellapi-shellexecutew)
 /// fucntion.
 #[inline]
-pub fn open_browser_internal(browser: Browser, url: &str) -> Result<ExitStatus> {
+pub fn open_browser_internal(
+    browser: Browser,
+    url: &str,
+    options: &BrowserOptions,
+) -> Result<OpenStatus> {
+    if options.incognito {
+        return Err(Error::new(
+            ErrorKind::Unsupported,
+            "incognito mode is not supported on this platform",
+        ));
+    }
     use winapi::shared::winerror::SUCCEEDED;
     use winapi::um::combaseapi::{CoInitializeEx, CoUninitialize};
     use winapi::um::objbase::{COINIT_APARTMENTTHREADED, COINIT_DISABLE_OLE1DDE};
     use winapi::um::shellapi::ShellExecuteW;
     use winapi::um::winuser::SW_SHOWNORMAL;
     match browser {
+        Browser::Default if options.dry_run => {
+            options.report_dry_run("ShellExecuteW", &["open".to_string(), url.to_string()]);
+            Ok(OpenStatus::launched())
+        }
         Browser::Default => {
             static OPEN: &[u16] = &['o' as u16, 'p' as u16, 'e' as u16, 'n' as u16, 0x0000];
-            let url =
+            let wide_url =
                 U16CString::from_str(url).map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
             let code = unsafe {
                 let coinitializeex_result = CoInitializeEx(
@@ -30,7 +47,7 @@ pub fn open_browser_internal(browser: Browser, url: &str) -> Result<ExitStatus>
                 let code = ShellExecuteW(
                     ptr::null_mut(),
                     OPEN.as_ptr(),
-                    url.as_ptr(),
+                    wide_url.as_ptr(),
                     ptr::null(),
                     ptr::null(),
                     SW_SHOWNORMAL,
@@ -41,14 +58,86 @@ pub fn open_browser_internal(browser: Browser, url: &str) -> Result<ExitStatus>
                 code
             };
             if code > 32 {
-                Ok(ExitStatus::from_raw(0))
+                // ShellExecuteW only hands off to the browser, there's no exit code to report
+                Ok(OpenStatus::launched())
             } else {
-                Err(Error::last_os_error())
+                let err = Error::last_os_error();
+                Err(Error::new(
+                    err.kind(),
+                    format!(
+                        "{}, to open it by hand, run: {}",
+                        err,
+                        shell_quote_command("start", &["", url])
+                    ),
+                ))
             }
         }
-        _ => Err(Error::new(
-            ErrorKind::NotFound,
-            "Only the default browser is supported on this platform right now",
-        )),
+        _ => Err(crate::unsupported_browser(browser)),
     }
 }
+
+/// Returns true if there's something which can open `browser`. Only the default browser is
+/// supported here, and `ShellExecuteW` is always around to hand over to it.
+pub fn is_available_internal(browser: Browser) -> bool {
+    browser == Browser::Default
+}
+
+/// Converts an absolute Windows path into a `file://` URL. Drive paths (`C:\x`) become
+/// `file:///C:/x`, while UNC paths (`\\server\share\x`) become `file://server/share/x`.
+pub fn file_url_internal(path: &Path) -> Result<String> {
+    let path = path
+        .to_str()
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "path is not valid unicode"))?;
+    Ok(windows_path_to_url(path))
+}
+
+fn windows_path_to_url(path: &str) -> String {
+    // verbatim paths, as produced by e.g. fs::canonicalize, are first turned into regular ones
+    let (path, unc) = if let Some(rest) = path.strip_prefix(r"\\?\UNC\") {
+        (rest, true)
+    } else if let Some(rest) = path.strip_prefix(r"\\?\") {
+        (rest, false)
+    } else if let Some(rest) = path.strip_prefix(r"\\") {
+        (rest, true)
+    } else {
+        (path, false)
+    };
+    let path = encode_url_path(&path.replace('\\', "/"));
+    if unc {
+        format!("file://{}", path)
+    } else {
+        format!("file:///{}", path)
+    }
+}
+
+#[test]
+fn test_drive_path_to_url() {
+    assert_eq!(
+        windows_path_to_url(r"C:\Users\me\report.html"),
+        "file:///C:/Users/me/report.html"
+    );
+    assert_eq!(
+        windows_path_to_url(r"\\?\C:\Users\me\report.html"),
+        "file:///C:/Users/me/report.html"
+    );
+}
+
+#[test]
+fn test_unc_path_to_url() {
+    assert_eq!(
+        windows_path_to_url(r"\\server\share\x.html"),
+        "file://server/share/x.html"
+    );
+    assert_eq!(
+        windows_path_to_url(r"\\?\UNC\server\share\x.html"),
+        "file://server/share/x.html"
+    );
+}
+
+#[test]
+fn test_path_with_spaces_to_url() {
+    assert_eq!(
+        windows_path_to_url(r"C:\My Documents\annual report.html"),
+        "file:///C:/My%20Documents/annual%20report.html"
+    );
+}
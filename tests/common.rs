@@ -28,7 +28,7 @@ pub async fn check_request_received(browser: Browser, uri: String) {
             .data(data.clone())
             .route("/*", web::get().to(log_handler))
     })
-    .bind(&bind_addr)
+    .bind(bind_addr)
     .unwrap_or_else(|_| panic!("Can not bind to {}", &bind_addr));
 
     let port = http_server
@@ -0,0 +1,69 @@
+#[cfg(not(target_arch = "wasm32"))]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_open_into_sink() {
+        let sink = env::temp_dir().join(format!("webbrowser-sink-{}.txt", std::process::id()));
+        let _ = fs::remove_file(&sink);
+        env::set_var("WEBBROWSER_TEST_SINK", &sink);
+
+        webbrowser::open("http://example.com/one").unwrap();
+        webbrowser::open_browser(webbrowser::Browser::Firefox, "http://example.com/two").unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        webbrowser::open_browser_with_deadline(
+            webbrowser::Browser::Default,
+            "http://example.com/three",
+            deadline,
+        )
+        .unwrap();
+        let err = webbrowser::open_browser(
+            webbrowser::Browser::InternetExplorer,
+            "http://example.com/x",
+        )
+        .unwrap_err();
+        assert!(err
+            .get_ref()
+            .and_then(|e| e.downcast_ref::<webbrowser::UnsupportedBrowserError>())
+            .is_some());
+        let mut expected = vec![
+            "http://example.com/one",
+            "http://example.com/two",
+            "http://example.com/three",
+        ];
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "freebsd",
+            target_os = "netbsd",
+            target_os = "openbsd",
+            target_os = "haiku"
+        ))]
+        {
+            let err = webbrowser::open_desktop_app("no-such-app.desktop", "http://example.com/x")
+                .unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+            // `true` is there on every unix, and would happily take any url
+            webbrowser::open_with_template("true --flag %s", "http://example.com/four").unwrap();
+            expected.push("http://example.com/four");
+        }
+
+        let contents = fs::read_to_string(&sink).unwrap();
+        fs::remove_file(&sink).unwrap();
+        let lines: Vec<(&str, &str)> = contents
+            .lines()
+            .map(|line| line.split_once('\t').unwrap())
+            .collect();
+        let urls: Vec<&str> = lines.iter().map(|(url, _)| *url).collect();
+        assert_eq!(urls, expected);
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "freebsd",
+            target_os = "netbsd",
+            target_os = "openbsd",
+            target_os = "haiku"
+        ))]
+        assert_eq!(lines[3].1, "true --flag http://example.com/four");
+    }
+}